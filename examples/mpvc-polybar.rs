@@ -3,10 +3,9 @@ use std::os::unix::fs::FileTypeExt as _;
 use std::path::{Path, PathBuf};
 use std::sync;
 
-use notify::{RecursiveMode, Watcher as _, event::{CreateKind, Event, EventKind}};
-use serde_json::Value;
+use notify::{RecursiveMode, Watcher as _, event::{CreateKind, Event as NotifyEvent, EventKind}};
 
-use mpvc::Mpv;
+use mpvc::{Event, Mpv, PlayerState};
 
 fn watch() -> Result<(), notify::Error> {
     let (tx, rx) = sync::mpsc::channel();
@@ -14,16 +13,23 @@ fn watch() -> Result<(), notify::Error> {
     watcher.watch(Path::new("/tmp"), RecursiveMode::NonRecursive)?;
     let path = PathBuf::from("/tmp/mpv.sock");
     for event in rx {
-        if let Ok(Event { kind: EventKind::Create(CreateKind::File), paths, .. }) = event && paths.contains(&path) {
+        if let Ok(NotifyEvent { kind: EventKind::Create(CreateKind::File), paths, .. }) = event && paths.contains(&path) {
             break;
         }
     }
     Ok(())
 }
 
-fn print(idle: bool, pause: Option<&str>, position: Option<u64>, count: Option<u64>, title: Option<&str>) {
-    if let (false, Some(p), Some(o), Some(c), Some(t)) = (idle, pause, position, count, title) {
-        println!("{p} #{o}/{c} - {t}");
+fn print(state: &PlayerState) {
+    if state.get_as::<bool>("idle-active").unwrap_or_default() {
+        println!("⏹ Stopped");
+    } else if let (Some(pause), Some(position), Some(count), Some(title)) = (
+        state.get_as::<bool>("pause"),
+        state.get_as::<u64>("playlist-pos-1"),
+        state.get_as::<u64>("playlist-count"),
+        state.get_as::<String>("media-title"),
+    ) {
+        println!("{} #{position}/{count} - {title}", if pause { "⏸" } else { "⏵" });
     }
 }
 
@@ -39,49 +45,19 @@ fn main() {
             let _ = watch();
         }
     };
+    // Keep running across player restarts, properties are observed again automatically
+    mpv.enable_reconnect("/tmp/mpv.sock");
 
     let properties = ["idle-active", "pause", "playlist-pos-1", "playlist-count", "media-title"];
-    for (i, property) in properties.into_iter().enumerate() {
-        if mpv.observe_property(i as isize + 1, property).is_err() {
-            return;
-        }
-    }
-
-    let mut idle = false;
-    let mut pause = Option::<&str>::None;
-    let mut position = Option::<u64>::None;
-    let mut count = Option::<u64>::None;
-    let mut title = Option::<String>::None;
-    while let Ok(event) = mpv.listen() {
-        if let Some(Value::String(e)) = event.get("event") && e == "property-change" {
-        } else {
-            continue;
-        }
+    let Ok(mut state) = PlayerState::new(&mut mpv, properties) else {
+        return;
+    };
+    print(&state);
 
-        let Some(Value::String(prop)) = event.get("name") else { continue; };
-        match prop.as_str() {
-            "idle-active" if let Some(Value::Bool(b)) = event.get("data") => {
-                idle = *b;
-                if idle {
-                    println!("⏹ Stopped");
-                }
-            }
-            "pause" if let Some(Value::Bool(b)) = event.get("data") => {
-                pause = Some(if *b { "⏸" } else { "⏵" });
-                print(idle, pause, position, count, title.as_deref());
-            }
-            "playlist-pos-1" if let Some(Value::Number(n)) = event.get("data") && let u @ Some(_) = n.as_u64() => {
-                position = u;
-                print(idle, pause, position, count, title.as_deref());
-            }
-            "playlist-count" if let Some(Value::Number(n)) = event.get("data") && let u @ Some(_) = n.as_u64() => {
-                count = u;
-                print(idle, pause, position, count, title.as_deref());
-            }
-            "media-title" if let Some(Value::String(str)) = event.get("data") => {
-                title = Some(str.clone());
-                print(idle, pause, position, count, title.as_deref());
-            }
+    while let Ok(event) = mpv.listen_event() {
+        match event {
+            Event::Shutdown => println!(),
+            event if state.apply(&event) => print(&state),
             _ => (),
         }
     }
@@ -0,0 +1,164 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::iter::once;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::UnixStream;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::router::{Router, reply_result};
+use crate::{Error, Event, MpvCommand, number};
+
+type AsyncRouter = Router<oneshot::Sender<Map<String, Value>>, mpsc::UnboundedSender<Event>>;
+
+/// An asynchronous connection to mpv, built on tokio.
+///
+/// A background task reads from the socket, hands replies to the pending commands by their
+/// `request_id`, and forwards events to every stream returned by [`AsyncMpv::events`]. All
+/// methods take `&self`, so the connection can be shared between tasks through an `Arc`.
+pub struct AsyncMpv {
+    writer: Mutex<OwnedWriteHalf>,
+    router: Arc<AsyncRouter>,
+    reader: JoinHandle<()>,
+}
+
+impl Debug for AsyncMpv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("AsyncMpv").finish_non_exhaustive()
+    }
+}
+
+impl Drop for AsyncMpv {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn read_loop(reader: OwnedReadHalf, router: Arc<AsyncRouter>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(response)) = lines.next_line().await {
+        router.dispatch(&response);
+    }
+    router.close();
+}
+
+impl AsyncMpv {
+    /// Connect to the mpv socket located at the given path. Must be called from within a tokio
+    /// runtime, which runs the background reader task.
+    pub async fn connect<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let (reader, writer) = UnixStream::connect(path).await.map_err(Error::ConnectError)?.into_split();
+        let router = Arc::new(Router::new());
+        Ok(Self {
+            writer: Mutex::new(writer),
+            reader: tokio::spawn(read_loop(reader, Arc::clone(&router))),
+            router,
+        })
+    }
+
+    async fn _command(&self, command: Value) -> Result<Value, Error> {
+        let (tx, rx) = oneshot::channel();
+        let (request_id, request) = self.router.register(command.clone(), tx)?;
+        if let Err(e) = self.writer.lock().await.write_all(request.as_bytes()).await {
+            self.router.cancel(request_id);
+            return Err(Error::WriteError(e));
+        }
+        reply_result(rx.await.ok(), &command)
+    }
+
+    /// Run an mpv command and return its result. See [`Mpv::command_value`](crate::Mpv::command_value).
+    ///
+    /// ```no_run
+    /// # use mpvc::{AsyncMpv, Error};
+    /// # async fn example() -> Result<(), Error> {
+    /// let mpv = AsyncMpv::connect("/tmp/mpvsocket").await?;
+    /// let path = mpv.command_value("expand-path", ["~~/watch_later"]).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn command_value<I: IntoIterator>(&self, command: &str, args: I) -> Result<Value, Error>
+    where I::Item: Into<Value> {
+        self._command(once(command.into()).chain(args.into_iter().map(Into::into)).collect()).await
+    }
+
+    /// Run an mpv command, discarding its result.
+    pub async fn command_arg<I: IntoIterator>(&self, command: &str, args: I) -> Result<(), Error>
+    where I::Item: Into<Value> {
+        self.command_value(command, args).await.map(|_| ())
+    }
+
+    /// Run an mpv command without any arguments.
+    pub async fn command(&self, command: &str) -> Result<(), Error> {
+        self._command(Value::Array(vec![command.into()])).await.map(|_| ())
+    }
+
+    /// Run a typed mpv command and return its result. See [`Mpv::execute`](crate::Mpv::execute).
+    pub async fn execute(&self, command: &MpvCommand) -> Result<Value, Error> {
+        self._command(command.try_into()?).await
+    }
+
+    /// Retrieve a property from mpv.
+    pub async fn get_property(&self, property: &str) -> Result<Value, Error> {
+        self._command(Value::Array(vec!["get_property".into(), property.into()])).await
+    }
+
+    /// Retrieve a property from mpv and deserialize it into the given type.
+    pub async fn get_property_as<T: DeserializeOwned>(&self, property: &str) -> Result<T, Error> {
+        serde_json::from_value(self.get_property(property).await?).map_err(Error::JsonError)
+    }
+
+    /// Set an mpv property to the given value.
+    pub async fn set_property<T: Into<Value>>(&self, property: &str, value: T) -> Result<(), Error> {
+        self._command(Value::Array(vec!["set_property".into(), property.into(), value.into()])).await.map(|_| ())
+    }
+
+    /// Add the given value to an mpv property. Runs the 'add' mpv command.
+    pub async fn add_property(&self, property: &str, value: f64) -> Result<(), Error> {
+        self._command(Value::Array(vec!["add".into(), property.into(), number(value)?])).await.map(|_| ())
+    }
+
+    /// Multiply an mpv property by the given value. Runs the 'multiply' mpv command.
+    pub async fn multiply_property(&self, property: &str, value: f64) -> Result<(), Error> {
+        self._command(Value::Array(vec!["multiply".into(), property.into(), number(value)?])).await.map(|_| ())
+    }
+
+    /// Watch a property for changes. Runs the `observe_property` mpv command.
+    pub async fn observe_property(&self, id: isize, property: &str) -> Result<(), Error> {
+        self._command(Value::Array(vec!["observe_property".into(), id.into(), property.into()])).await.map(|_| ())
+    }
+
+    /// Undo the corresponding `observe_property`. Runs the `unobserve_property` mpv command.
+    pub async fn unobserve_property(&self, id: isize) -> Result<(), Error> {
+        self._command(Value::Array(vec!["unobserve_property".into(), id.into()])).await.map(|_| ())
+    }
+
+    /// Subscribe to the events sent by mpv. Every stream receives all events which arrive after
+    /// it was created, and ends when the connection is closed.
+    ///
+    /// ```no_run
+    /// # use mpvc::{AsyncMpv, Error, Event};
+    /// # use tokio_stream::StreamExt as _;
+    /// # async fn example() -> Result<(), Error> {
+    /// let mpv = AsyncMpv::connect("/tmp/mpvsocket").await?;
+    /// let mut events = mpv.events();
+    /// mpv.observe_property(1, "pause").await?;
+    /// while let Some(event) = events.next().await {
+    ///     if let Event::PropertyChange { name, data, .. } = event {
+    ///         println!("{name}: {data}");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Stream<Item = Event> + use<> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.router.subscribe(tx);
+        UnboundedReceiverStream::new(rx)
+    }
+}
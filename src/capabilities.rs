@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{Error, Mpv, Transport};
+
+/// A version of mpv, as parsed from the `mpv-version` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MpvVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl MpvVersion {
+    /// Parse a version such as `mpv 0.38.0` or `mpv v0.37.0-412-g1a2b3c4d`.
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.strip_prefix("mpv ").unwrap_or(version);
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let end = version.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(version.len());
+        let mut numbers = version[..end].split('.').map(str::parse::<u32>);
+        Some(Self {
+            major: numbers.next()?.ok()?,
+            minor: numbers.next()?.ok()?,
+            patch: numbers.next().and_then(Result::ok).unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct CommandInfo {
+    name: String,
+    #[serde(default)]
+    args: Vec<ArgumentInfo>,
+}
+
+#[derive(Deserialize)]
+struct ArgumentInfo {
+    name: String,
+}
+
+/// What the connected mpv supports, queried once from its `mpv-version`, `command-list` and
+/// `property-list` properties. See [`Mpv::capabilities`].
+///
+/// Information mpv doesn't provide is treated as unknown, in which case every command or
+/// property is assumed to be supported.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    version: Option<String>,
+    /// The names of the arguments of every command.
+    commands: Option<BTreeMap<String, Vec<String>>>,
+    properties: Option<BTreeSet<String>>,
+}
+
+/// The value of a queried property, or `None` if mpv doesn't have it.
+fn known<T, F: FnOnce(Value) -> Option<T>>(result: Result<Value, Error>, parse: F) -> Result<Option<T>, Error> {
+    match result {
+        Ok(value) => Ok(parse(value)),
+        Err(Error::MpvError(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Capabilities {
+    /// The capabilities of the latest mpv, which is assumed to support every command, argument
+    /// and property.
+    pub fn latest() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn query<S: Transport>(mpv: &mut Mpv<S>) -> Result<Self, Error> {
+        let mut results = mpv.command_batch([
+            json!(["get_property", "mpv-version"]),
+            json!(["get_property", "command-list"]),
+            json!(["get_property", "property-list"]),
+        ])?.into_iter();
+        let mut next = || results.next().unwrap_or(Err(Error::MissingValue));
+        let version = known(next(), |value| value.as_str().map(str::to_owned))?;
+        let commands = known(next(), |value| {
+            let commands = serde_json::from_value::<Vec<CommandInfo>>(value).ok()?;
+            Some(commands.into_iter()
+                .map(|command| (command.name, command.args.into_iter().map(|arg| arg.name).collect()))
+                .collect())
+        })?;
+        let properties = known(next(), |value| serde_json::from_value(value).ok())?;
+        Ok(Self { version, commands, properties })
+    }
+
+    /// The version string of mpv, e.g. `mpv 0.38.0`.
+    pub fn version_string(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// The version of mpv, if it could be parsed.
+    pub fn version(&self) -> Option<MpvVersion> {
+        MpvVersion::parse(self.version.as_deref()?)
+    }
+
+    /// Whether mpv has the given input command.
+    pub fn supports_command(&self, command: &str) -> bool {
+        self.commands.as_ref().is_none_or(|commands| commands.contains_key(command))
+    }
+
+    /// Whether mpv has the given input command, and it takes an argument with the given name.
+    /// For example, `loadfile` only takes an `index` since mpv 0.38.
+    pub fn supports_argument(&self, command: &str, argument: &str) -> bool {
+        self.commands.as_ref().is_none_or(|commands| {
+            commands.get(command).is_some_and(|args| args.iter().any(|arg| arg == argument))
+        })
+    }
+
+    /// Whether mpv has the given property. Sub-properties such as `metadata/by-key/Artist` are
+    /// looked up by their top-level property.
+    pub fn supports_property(&self, property: &str) -> bool {
+        let property = property.split_once('/').map_or(property, |(property, _)| property);
+        self.properties.as_ref().is_none_or(|properties| properties.contains(property))
+    }
+}
@@ -0,0 +1,291 @@
+use core::iter::once;
+use core::time::Duration;
+
+use serde_json::{Map, Value};
+
+use crate::{Error, LoadMode, Seek, number};
+
+/// How a track added with [`MpvCommand::SubAdd`] or [`MpvCommand::AudioAdd`] is selected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrackFlag {
+    /// Select the track immediately.
+    #[default]
+    Select,
+    /// Add the track without selecting it.
+    Auto,
+    /// Select an already added track with the same file name instead of adding it again.
+    Cached,
+}
+
+impl TrackFlag {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Select => "select",
+            Self::Auto => "auto",
+            Self::Cached => "cached",
+        }
+    }
+}
+
+/// How [`MpvCommand::Af`] and [`MpvCommand::Vf`] change the filter chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterOperation {
+    /// Replace the filter chain.
+    Set,
+    /// Append a filter to the chain.
+    Append,
+    /// Append filters to the chain, which may be given as a list.
+    Add,
+    /// Prepend filters to the chain.
+    Pre,
+    /// Remove filters from the chain.
+    Remove,
+    /// Remove all filters, the value is ignored.
+    Clear,
+    /// Add a filter if it isn't in the chain yet, and remove it otherwise.
+    Toggle,
+}
+
+impl FilterOperation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Set => "set",
+            Self::Append => "append",
+            Self::Add => "add",
+            Self::Pre => "pre",
+            Self::Remove => "remove",
+            Self::Clear => "clr",
+            Self::Toggle => "toggle",
+        }
+    }
+}
+
+/// An mpv input command, see mpv's manual for what each of them does.
+///
+/// Commands convert into the JSON sent over IPC with `Value::try_from`, and are run with
+/// [`Mpv::execute`](crate::Mpv::execute) or passed to [`Mpv::command_batch`](crate::Mpv::command_batch).
+/// The conversion fails with [`Error::UnexpectedValue`] if a number is infinite or NaN, as JSON
+/// can't represent these. Arguments are passed by position, unless an optional argument is left
+/// out before a given one, in which case mpv's named argument form is used.
+///
+/// ```no_run
+/// # use mpvc::{Error, LoadMode, Mpv, MpvCommand};
+/// # fn main() -> Result<(), Error> {
+/// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+/// mpv.execute(&MpvCommand::LoadFile {
+///     url: "song.flac".to_owned(),
+///     flags: LoadMode::Append,
+///     options: vec![("start".to_owned(), "30".to_owned())],
+/// })?;
+/// mpv.execute(&MpvCommand::ShowText { text: "Added".to_owned(), duration: None, level: None })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MpvCommand {
+    /// Load a file. The index of [`LoadMode::InsertAt`] is passed as the `index` argument (mpv
+    /// 0.38 or later), and `options` are set for the duration of the file.
+    LoadFile { url: String, flags: LoadMode, options: Vec<(String, String)> },
+    /// Load the entries of a playlist file.
+    LoadList { url: String, flags: LoadMode },
+    /// Change the playback position. `exact` forces precise or keyframe seeking instead of the
+    /// `hr-seek` default.
+    Seek { target: Seek, exact: Option<bool> },
+    /// Play the next entry. Unless `force` is set, nothing happens at the end of the playlist.
+    PlaylistNext { force: bool },
+    /// Play the previous entry. Unless `force` is set, nothing happens at the start of the playlist.
+    PlaylistPrev { force: bool },
+    /// Play the entry at the given index (starting at 0).
+    PlaylistPlayIndex(u64),
+    /// Remove the entry at the given index, or the current entry if `None`.
+    PlaylistRemove(Option<u64>),
+    /// Move the entry at the index `from` to the index `to`.
+    PlaylistMove { from: u64, to: u64 },
+    /// Clear the playlist, except for the current entry.
+    PlaylistClear,
+    /// Shuffle the playlist.
+    PlaylistShuffle,
+    /// Undo the last shuffle of the playlist.
+    PlaylistUnshuffle,
+    /// Stop playback, and clear the playlist unless `keep_playlist` is set.
+    Stop { keep_playlist: bool },
+    /// Quit mpv, optionally with the given exit code.
+    Quit(Option<i32>),
+    /// Set a property from a string, as in `input.conf`.
+    Set { property: String, value: String },
+    /// Add to a numeric property.
+    Add { property: String, value: f64 },
+    /// Multiply a numeric property.
+    Multiply { property: String, factor: f64 },
+    /// Cycle a property through its values, backwards if `down` is set.
+    Cycle { property: String, down: bool },
+    /// Cycle a property through the given values.
+    CycleValues { property: String, values: Vec<Value>, reverse: bool },
+    /// Send a message to all clients and scripts.
+    ScriptMessage(Vec<String>),
+    /// Send a message to the client or script with the given name.
+    ScriptMessageTo { target: String, args: Vec<String> },
+    /// Show text on the OSD, for the default duration if `duration` is `None`.
+    ShowText { text: String, duration: Option<Duration>, level: Option<u8> },
+    /// Load a subtitle file, with an optional track title and language.
+    SubAdd { url: String, flags: TrackFlag, title: Option<String>, lang: Option<String> },
+    /// Load an audio file, with an optional track title and language.
+    AudioAdd { url: String, flags: TrackFlag, title: Option<String>, lang: Option<String> },
+    /// Change the audio filter chain.
+    Af { operation: FilterOperation, value: String },
+    /// Change the video filter chain.
+    Vf { operation: FilterOperation, value: String },
+    /// Bind a key to a command, as in `input.conf`.
+    Keybind { key: String, command: String },
+}
+
+enum Args {
+    /// Named arguments in positional order, `None` for optional arguments left out.
+    Named(Vec<(&'static str, Option<Value>)>),
+    /// Commands taking a variable amount of arguments, which can only be passed by position.
+    Variadic(Vec<Value>),
+}
+
+fn flag(set: bool, value: &str) -> Option<Value> {
+    set.then(|| value.into())
+}
+
+impl MpvCommand {
+    /// The name of the command, e.g. `"loadfile"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::LoadFile { .. } => "loadfile",
+            Self::LoadList { .. } => "loadlist",
+            Self::Seek { .. } => "seek",
+            Self::PlaylistNext { .. } => "playlist-next",
+            Self::PlaylistPrev { .. } => "playlist-prev",
+            Self::PlaylistPlayIndex(_) => "playlist-play-index",
+            Self::PlaylistRemove(_) => "playlist-remove",
+            Self::PlaylistMove { .. } => "playlist-move",
+            Self::PlaylistClear => "playlist-clear",
+            Self::PlaylistShuffle => "playlist-shuffle",
+            Self::PlaylistUnshuffle => "playlist-unshuffle",
+            Self::Stop { .. } => "stop",
+            Self::Quit(_) => "quit",
+            Self::Set { .. } => "set",
+            Self::Add { .. } => "add",
+            Self::Multiply { .. } => "multiply",
+            Self::Cycle { .. } => "cycle",
+            Self::CycleValues { .. } => "cycle-values",
+            Self::ScriptMessage(_) => "script-message",
+            Self::ScriptMessageTo { .. } => "script-message-to",
+            Self::ShowText { .. } => "show-text",
+            Self::SubAdd { .. } => "sub-add",
+            Self::AudioAdd { .. } => "audio-add",
+            Self::Af { .. } => "af",
+            Self::Vf { .. } => "vf",
+            Self::Keybind { .. } => "keybind",
+        }
+    }
+
+    fn args(&self) -> Result<Args, Error> {
+        Ok(match *self {
+            Self::LoadFile { ref url, flags, ref options } => Args::Named(vec![
+                ("url", Some(url.as_str().into())),
+                ("flags", Some(flags.as_str().into())),
+                ("index", flags.index().map(Into::into)),
+                ("options", (!options.is_empty()).then(|| {
+                    options.iter().map(|(k, v)| (k.clone(), v.as_str().into())).collect::<Map<_, _>>().into()
+                })),
+            ]),
+            Self::LoadList { ref url, flags } => Args::Named(vec![
+                ("url", Some(url.as_str().into())),
+                ("flags", Some(flags.as_str().into())),
+                ("index", flags.index().map(Into::into)),
+            ]),
+            Self::Seek { target, exact } => {
+                let (target, mode) = match target {
+                    Seek::Relative(target) => (target, "relative"),
+                    Seek::Absolute(target) => (target, "absolute"),
+                    Seek::RelativePercent(target) => (target, "relative-percent"),
+                    Seek::AbsolutePercent(target) => (target, "absolute-percent"),
+                };
+                let flags = match exact {
+                    Some(true) => format!("{mode}+exact"),
+                    Some(false) => format!("{mode}+keyframes"),
+                    None => mode.to_owned(),
+                };
+                Args::Named(vec![("target", Some(number(target)?)), ("flags", Some(flags.into()))])
+            }
+            Self::PlaylistNext { force } | Self::PlaylistPrev { force } => Args::Named(vec![("flags", flag(force, "force"))]),
+            Self::PlaylistPlayIndex(index) => Args::Named(vec![("index", Some(index.into()))]),
+            Self::PlaylistRemove(index) => Args::Named(vec![("index", Some(index.map_or_else(|| "current".into(), Into::into)))]),
+            Self::PlaylistMove { from, to } => Args::Named(vec![("index1", Some(from.into())), ("index2", Some(to.into()))]),
+            Self::PlaylistClear | Self::PlaylistShuffle | Self::PlaylistUnshuffle => Args::Named(Vec::new()),
+            Self::Stop { keep_playlist } => Args::Named(vec![("flags", flag(keep_playlist, "keep-playlist"))]),
+            Self::Quit(code) => Args::Named(vec![("code", code.map(Into::into))]),
+            Self::Set { ref property, ref value } => Args::Named(vec![
+                ("name", Some(property.as_str().into())),
+                ("value", Some(value.as_str().into())),
+            ]),
+            Self::Add { ref property, value } => Args::Named(vec![("name", Some(property.as_str().into())), ("value", Some(number(value)?))]),
+            Self::Multiply { ref property, factor } => Args::Named(vec![("name", Some(property.as_str().into())), ("value", Some(number(factor)?))]),
+            Self::Cycle { ref property, down } => Args::Named(vec![("name", Some(property.as_str().into())), ("value", flag(down, "down"))]),
+            Self::CycleValues { ref property, ref values, reverse } => Args::Variadic(
+                flag(reverse, "!reverse").into_iter()
+                    .chain(once(property.as_str().into()))
+                    .chain(values.iter().cloned())
+                    .collect(),
+            ),
+            Self::ScriptMessage(ref args) => Args::Variadic(args.iter().map(|arg| arg.as_str().into()).collect()),
+            Self::ScriptMessageTo { ref target, ref args } => Args::Variadic(
+                once(target.as_str().into()).chain(args.iter().map(|arg| arg.as_str().into())).collect(),
+            ),
+            Self::ShowText { ref text, duration, level } => Args::Named(vec![
+                ("text", Some(text.as_str().into())),
+                ("duration", duration.map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX).into())),
+                ("level", level.map(Into::into)),
+            ]),
+            Self::SubAdd { ref url, flags, ref title, ref lang } | Self::AudioAdd { ref url, flags, ref title, ref lang } => Args::Named(vec![
+                ("url", Some(url.as_str().into())),
+                ("flags", Some(flags.as_str().into())),
+                ("title", title.as_deref().map(Into::into)),
+                ("lang", lang.as_deref().map(Into::into)),
+            ]),
+            Self::Af { operation, ref value } | Self::Vf { operation, ref value } => Args::Named(vec![
+                ("operation", Some(operation.as_str().into())),
+                ("value", Some(value.as_str().into())),
+            ]),
+            Self::Keybind { ref key, ref command } => Args::Named(vec![
+                ("name", Some(key.as_str().into())),
+                ("cmd", Some(command.as_str().into())),
+            ]),
+        })
+    }
+}
+
+impl TryFrom<&MpvCommand> for Value {
+    type Error = Error;
+
+    fn try_from(command: &MpvCommand) -> Result<Self, Error> {
+        let name = command.name();
+        Ok(match command.args()? {
+            Args::Variadic(args) => once(name.into()).chain(args).collect(),
+            Args::Named(args) => {
+                let given = args.iter().rposition(|(_, value)| value.is_some()).map_or(0, |i| i + 1);
+                if args[..given].iter().all(|(_, value)| value.is_some()) {
+                    once(name.into()).chain(args.into_iter().filter_map(|(_, value)| value)).collect()
+                } else {
+                    once(("name".to_owned(), name.into()))
+                        .chain(args.into_iter().filter_map(|(key, value)| Some((key.to_owned(), value?))))
+                        .collect::<Map<_, _>>()
+                        .into()
+                }
+            }
+        })
+    }
+}
+
+impl TryFrom<MpvCommand> for Value {
+    type Error = Error;
+
+    fn try_from(command: MpvCommand) -> Result<Self, Error> {
+        Self::try_from(&command)
+    }
+}
@@ -0,0 +1,105 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use std::collections::VecDeque;
+use std::io::{Read, Result as IoResult, Write};
+
+use serde_json::{Map, Value, json};
+
+use crate::Transport;
+
+/// A [`Transport`] which prints every request instead of sending it to mpv, for seeing which
+/// commands would be run.
+///
+/// Each request is written to the output as a line of JSON, exactly as it would be sent. Replies
+/// are made up from a stub state: `get_property` returns the stubbed value of the property, or
+/// fails with `property unavailable` if there is none, `set_property` updates the stub, and every
+/// other command succeeds without a result. No events are ever received.
+///
+/// ```no_run
+/// # use std::io;
+/// # use mpvc::{DryRun, Error, Mpv};
+/// # fn main() -> Result<(), Error> {
+/// let mut dry_run = DryRun::new(io::stdout());
+/// dry_run.stub("playlist-count", 3);
+/// let mut mpv = Mpv::new(dry_run);
+/// let count = mpv.get_property_as::<u64>("playlist-count")?;
+/// mpv.command_arg("playlist-move", [count - 1, 0])?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DryRun {
+    output: Box<dyn Write + Send>,
+    properties: Map<String, Value>,
+    /// A partially written request.
+    request: Vec<u8>,
+    replies: VecDeque<u8>,
+}
+
+impl Debug for DryRun {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("DryRun").field("properties", &self.properties).finish_non_exhaustive()
+    }
+}
+
+impl DryRun {
+    /// Print requests to the given writer, starting with an empty stub state.
+    pub fn new<W: Write + Send + 'static>(output: W) -> Self {
+        Self {
+            output: Box::new(output),
+            properties: Map::new(),
+            request: Vec::new(),
+            replies: VecDeque::new(),
+        }
+    }
+
+    /// Set the value `get_property` returns for the given property.
+    pub fn stub<T: Into<Value>>(&mut self, property: &str, value: T) -> &mut Self {
+        self.properties.insert(property.to_owned(), value.into());
+        self
+    }
+
+    fn reply(&mut self, request: &Value) -> Value {
+        let command = request.get("command").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        let property = command.get(1).and_then(Value::as_str);
+        let mut reply = match (command.first().and_then(Value::as_str), property) {
+            (Some("get_property"), Some(property)) => match self.properties.get(property) {
+                Some(data) => json!({ "error": "success", "data": data }),
+                None => json!({ "error": "property unavailable" }),
+            },
+            (Some("set_property"), Some(property)) => {
+                if let Some(value) = command.get(2) {
+                    self.properties.insert(property.to_owned(), value.clone());
+                }
+                json!({ "error": "success" })
+            }
+            _ => json!({ "error": "success" }),
+        };
+        reply["request_id"] = request.get("request_id").cloned().unwrap_or_default();
+        reply
+    }
+}
+
+impl Read for DryRun {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.replies.read(buf)
+    }
+}
+
+impl Write for DryRun {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.request.extend_from_slice(buf);
+        while let Some(i) = self.request.iter().position(|&b| b == b'\n') {
+            let line = self.request.drain(..=i).collect::<Vec<_>>();
+            self.output.write_all(&line)?;
+            let request = serde_json::from_slice::<Value>(&line).unwrap_or_default();
+            let reply = self.reply(&request);
+            self.replies.extend((reply.to_string() + "\n").as_bytes());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.output.flush()
+    }
+}
+
+impl Transport for DryRun {}
@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The reason a file was unloaded, as reported by the `end-file` event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndFileReason {
+    Eof,
+    Stop,
+    Quit,
+    Error,
+    Redirect,
+    #[serde(other)]
+    Unknown,
+}
+
+/// The level of a log message, ordered from the most to the least severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    /// Status line updates, like the playback status printed by the terminal output.
+    Status,
+    #[serde(rename = "v")]
+    Verbose,
+    Debug,
+    Trace,
+    /// Statistics used for profiling, only logged when mpv is built with them.
+    Stats,
+}
+
+impl LogLevel {
+    /// The name of the level as used by mpv, e.g. `"warn"` or `"v"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fatal => "fatal",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Status => "status",
+            Self::Verbose => "v",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+            Self::Stats => "stats",
+        }
+    }
+}
+
+/// A message from mpv's log, see [`Mpv::request_log_messages`](crate::Mpv::request_log_messages).
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct LogMessage {
+    /// The module which logged the message, e.g. `cplayer` or the name of a script.
+    pub prefix: String,
+    pub level: LogLevel,
+    /// The message, usually ending with a newline.
+    pub text: String,
+}
+
+/// An event sent by mpv. Events which are not covered by a dedicated variant are returned as
+/// [`Event::Unknown`] with the raw JSON object.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    StartFile {
+        playlist_entry_id: Option<i64>,
+    },
+    EndFile {
+        reason: EndFileReason,
+        playlist_entry_id: Option<i64>,
+        file_error: Option<String>,
+    },
+    FileLoaded,
+    Seek,
+    PlaybackRestart,
+    Shutdown,
+    PropertyChange {
+        #[serde(default)]
+        id: i64,
+        name: String,
+        #[serde(default)]
+        data: Value,
+    },
+    ClientMessage {
+        args: Vec<String>,
+    },
+    LogMessage(LogMessage),
+    Hook {
+        #[serde(default)]
+        id: i64,
+        hook_id: u64,
+    },
+    /// Not sent by mpv, but emitted after the connection was re-established. See
+    /// [`Mpv::enable_reconnect`](crate::Mpv::enable_reconnect).
+    Reconnected,
+    #[serde(untagged)]
+    Unknown(Map<String, Value>),
+}
+
+impl Event {
+    /// The name of the event as used by mpv, e.g. `"property-change"`.
+    pub fn name(&self) -> &str {
+        match *self {
+            Self::StartFile { .. } => "start-file",
+            Self::EndFile { .. } => "end-file",
+            Self::FileLoaded => "file-loaded",
+            Self::Seek => "seek",
+            Self::PlaybackRestart => "playback-restart",
+            Self::Shutdown => "shutdown",
+            Self::PropertyChange { .. } => "property-change",
+            Self::ClientMessage { .. } => "client-message",
+            Self::LogMessage { .. } => "log-message",
+            Self::Hook { .. } => "hook",
+            Self::Reconnected => "reconnected",
+            Self::Unknown(ref map) => map.get("event").and_then(Value::as_str).unwrap_or_default(),
+        }
+    }
+}
@@ -1,44 +1,215 @@
 use core::error::Error as StdError;
 use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use core::iter::once;
-use std::io::{BufRead as _, BufReader, Error as IoError, ErrorKind as IoErrorKind, Write as _};
+use core::mem;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::{BufRead as _, BufReader, Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt as _;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::SocketAddr;
 use std::os::unix::net::UnixStream;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Instant;
 
 use log::debug;
+use serde::de::DeserializeOwned;
 use serde_json::{Error as JsonError, Map, Number, Value};
 
-pub struct Mpv {
-    reader: BufReader<UnixStream>,
+#[cfg(feature = "tokio")]
+mod async_client;
+mod capabilities;
+mod command;
+mod dry_run;
+mod event;
+mod observer;
+mod player;
+mod process;
+mod property;
+mod router;
+mod shared;
+mod state;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transcript;
+mod transport;
+
+#[cfg(feature = "tokio")]
+pub use async_client::AsyncMpv;
+pub use capabilities::{Capabilities, MpvVersion};
+pub use command::{FilterOperation, MpvCommand, TrackFlag};
+pub use dry_run::DryRun;
+pub use event::{EndFileReason, Event, LogLevel, LogMessage};
+pub use observer::Observer;
+pub use player::{LoadMode, Player, Seek};
+pub use process::MpvProcess;
+pub use property::{AudioDevice, Chapter, Metadata, PlaylistEntry, Track};
+pub use shared::SharedMpv;
+pub use state::{Change, PlayerState};
+pub use transport::Transport;
+
+use transcript::Recorder;
+
+type Reconnect<S> = Box<dyn FnMut() -> IoResult<S> + Send>;
+type HookCallback<S> = Box<dyn FnMut(&mut Mpv<S>) -> Result<(), Error> + Send>;
+type MessageCallback<S> = Box<dyn FnMut(&mut Mpv<S>, &[String]) -> Result<(), Error> + Send>;
+
+/// A hook registered with [`Mpv::hook_add`].
+struct Hook<S: Transport> {
+    name: String,
+    priority: i64,
+    /// Taken out while the callback runs.
+    callback: Option<HookCallback<S>>,
+}
+
+/// The id of the first property observed with [`Mpv::observe`], far from ids picked by hand.
+const FIRST_OBSERVER_ID: isize = 1 << 30;
+
+/// The id of the first hook registered with [`Mpv::hook_add`].
+const FIRST_HOOK_ID: u64 = 1 << 30;
+
+/// A connection to mpv over a [`Transport`], a Unix socket by default.
+pub struct Mpv<S: Transport = UnixStream> {
+    reader: BufReader<S>,
     responses: Vec<Map<String, Value>>,
     counter: i64,
+    timeout: Option<Duration>,
+    partial: Vec<u8>,
+    observed: Vec<(isize, String)>,
+    reconnect: Option<Reconnect<S>>,
+    recorder: Option<Recorder>,
+    next_observer: isize,
+    dropped: Arc<Mutex<Vec<isize>>>,
+    hooks: Vec<Hook<S>>,
+    /// Handlers registered with [`Mpv::on_message`], taken out while they run.
+    messages: BTreeMap<String, Option<MessageCallback<S>>>,
+    /// Events enabled or disabled with [`Mpv::enable_event`] and [`Mpv::disable_event`], in the
+    /// order they have to be applied.
+    event_filter: Vec<(String, bool)>,
+    capabilities: Option<Capabilities>,
 }
 
-impl Debug for Mpv {
+impl<S: Transport + Debug> Debug for Mpv<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let mut builder = f.debug_struct("Mpv");
-        if let Ok(addr) = self.reader.get_ref().peer_addr() && let Some(pathname) = addr.as_pathname() {
-            builder.field("path", &pathname);
+        f.debug_struct("Mpv").field("transport", self.reader.get_ref()).finish_non_exhaustive()
+    }
+}
+
+/// The kind of an error reported by mpv, parsed from its error message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MpvErrorKind {
+    /// `invalid parameter`, e.g. an unknown command or a malformed argument.
+    InvalidParameter,
+    /// `property not found`: no property with the given name exists.
+    PropertyNotFound,
+    /// `property unavailable`: the property exists, but has no value right now, e.g. because
+    /// nothing is playing.
+    PropertyUnavailable,
+    /// `unsupported format for accessing property`: the value has the wrong type.
+    PropertyFormat,
+    /// `error accessing property`
+    PropertyError,
+    /// `option not found`
+    OptionNotFound,
+    /// `unsupported format for accessing option`
+    OptionFormat,
+    /// `error setting option`
+    OptionError,
+    /// `error running command`
+    CommandFailed,
+    /// `error loading file`, `unrecognized file format` and similar errors about playing a file.
+    LoadingFailed,
+    /// `things that should happen are not yet implemented`
+    Unsupported,
+    /// Any other error message.
+    Other,
+}
+
+impl MpvErrorKind {
+    fn parse(message: &str) -> Self {
+        match message {
+            "invalid parameter" => Self::InvalidParameter,
+            "property not found" => Self::PropertyNotFound,
+            "property unavailable" => Self::PropertyUnavailable,
+            "unsupported format for accessing property" => Self::PropertyFormat,
+            "error accessing property" => Self::PropertyError,
+            "option not found" => Self::OptionNotFound,
+            "unsupported format for accessing option" => Self::OptionFormat,
+            "error setting option" => Self::OptionError,
+            "error running command" => Self::CommandFailed,
+            "error loading file" | "unrecognized file format" | "no audio or video data played"
+                | "audio output initialization failed" | "video output initialization failed" => Self::LoadingFailed,
+            "things that should happen are not yet implemented" => Self::Unsupported,
+            _ => Self::Other,
         }
-        builder.finish()
+    }
+}
+
+/// An error reply from mpv, along with the command which caused it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MpvError {
+    pub kind: MpvErrorKind,
+    /// The error message as sent by mpv.
+    pub message: String,
+    /// The failing command, as a JSON array of its name and arguments, or an object if it was
+    /// sent with named arguments.
+    pub command: Value,
+}
+
+impl MpvError {
+    fn new(message: &str, command: &Value) -> Self {
+        Self {
+            kind: MpvErrorKind::parse(message),
+            message: message.to_owned(),
+            command: command.clone(),
+        }
+    }
+
+    /// The name of the property the failing command accessed, if it is one of the property
+    /// commands.
+    pub fn property(&self) -> Option<&str> {
+        let index = match self.command.get(0)?.as_str()? {
+            "get_property" | "get_property_string" | "set_property" | "set_property_string"
+                | "add" | "multiply" | "cycle" | "cycle-values" => 1,
+            "observe_property" | "observe_property_string" => 2,
+            _ => return None,
+        };
+        self.command.get(index)?.as_str()
+    }
+}
+
+impl StdError for MpvError {}
+
+impl Display for MpvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ({})", self.message, self.command)
     }
 }
 
 pub enum Error {
-    MpvError(String),
+    MpvError(MpvError),
     ConnectError(IoError),
+    SpawnError(IoError),
     ReadError(IoError),
     WriteError(IoError),
     JsonError(JsonError),
     UnexpectedValue,
     MissingValue,
+    Timeout,
 }
 
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match *self {
-            Self::MpvError(_) | Self::UnexpectedValue | Self::MissingValue => None,
-            Self::ConnectError(ref e) | Self::ReadError(ref e) | Self::WriteError(ref e) => Some(e),
+            Self::UnexpectedValue | Self::MissingValue | Self::Timeout => None,
+            Self::MpvError(ref e) => Some(e),
+            Self::ConnectError(ref e) | Self::SpawnError(ref e) | Self::ReadError(ref e) | Self::WriteError(ref e) => Some(e),
             Self::JsonError(ref e) => Some(e),
         }
     }
@@ -49,11 +220,13 @@ impl Display for Error {
         match *self {
             Self::MpvError(ref e) => write!(f, "MpvError: {e}"),
             Self::ConnectError(ref e) => write!(f, "ConnectError: {e}"),
+            Self::SpawnError(ref e) => write!(f, "SpawnError: {e}"),
             Self::ReadError(ref e) => write!(f, "ReadError: {e}"),
             Self::WriteError(ref e) => write!(f, "WriteError: {e}"),
             Self::JsonError(ref e) => write!(f, "JsonError: {e}"),
             Self::UnexpectedValue => write!(f, "Unexpected value received"),
             Self::MissingValue => write!(f, "Missing value"),
+            Self::Timeout => write!(f, "Timed out waiting for mpv"),
         }
     }
 }
@@ -64,194 +237,1017 @@ impl Debug for Error {
     }
 }
 
+fn is_timeout(e: &IoError) -> bool {
+    matches!(e.kind(), IoErrorKind::WouldBlock | IoErrorKind::TimedOut)
+}
+
+fn is_disconnect(e: &Error) -> bool {
+    matches!(*e, Error::ReadError(_) | Error::WriteError(_))
+}
+
+/// Whether the event is a change of the property observed under the given id.
+fn is_change_of(event: &Map<String, Value>, id: isize) -> bool {
+    event.get("event").and_then(Value::as_str) == Some("property-change")
+        && event.get("id").and_then(Value::as_i64) == Some(id as i64)
+}
+
+/// Convert a float into a JSON number. JSON can't represent infinity and NaN, which serde_json
+/// would otherwise turn into `null`.
+pub(crate) fn number(value: f64) -> Result<Value, Error> {
+    Number::from_f64(value).map(Value::Number).ok_or(Error::UnexpectedValue)
+}
+
+/// Serialize a command into a request line (without the trailing newline).
+fn encode_command(command: Value, request_id: i64) -> String {
+    Value::Object({
+        let mut map = Map::with_capacity(2);
+        map.insert("command".to_owned(), command);
+        map.insert("request_id".to_owned(), request_id.into());
+        map
+    }).to_string()
+}
+
+/// Extract the result of a command from its reply.
+fn reply_value(mut map: Map<String, Value>, command: &Value) -> Result<Value, Error> {
+    let error = if let Some(Value::String(error)) = map.get("error") {
+        Ok(error)
+    } else {
+        Err(Error::UnexpectedValue)
+    }?;
+
+    if error == "success" {
+        Ok(map.remove("data").unwrap_or(Value::Null))
+    } else {
+        Err(Error::MpvError(MpvError::new(error, command)))
+    }
+}
+
 impl Mpv {
     /// Connect to the mpv socket located at the given path.
     pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        match UnixStream::connect(path) {
-            Ok(sock) => Ok(Self {
-                reader: BufReader::new(sock),
-                responses: Vec::new(),
-                counter: -1,
-            }),
-            Err(e) => Err(Error::ConnectError(e)),
+        UnixStream::connect(path).map(Self::from_stream).map_err(Error::ConnectError)
+    }
+
+    /// Use an already connected stream, e.g. one end of a socket pair whose other end was handed
+    /// to mpv with `--input-ipc-client`.
+    pub fn from_stream(sock: UnixStream) -> Self {
+        Self::new(sock)
+    }
+
+    /// Connect to the mpv socket with the given name in the abstract namespace, as created by
+    /// `--input-ipc-server=@name`.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect_abstract("mpvsocket")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn connect_abstract<N: AsRef<[u8]>>(name: N) -> Result<Self, Error> {
+        SocketAddr::from_abstract_name(name)
+            .and_then(|addr| UnixStream::connect_addr(&addr))
+            .map(Self::from_stream)
+            .map_err(Error::ConnectError)
+    }
+
+    /// Spawn mpv with the given arguments and connect to it through a socket pair. This avoids
+    /// creating a socket in the filesystem, see [`MpvProcess::with_socketpair`].
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::spawn_with_socketpair(["--idle", "--no-terminal"])?;
+    /// mpv.command_arg("loadfile", ["music.flac"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_with_socketpair<I: IntoIterator>(args: I) -> Result<Self, Error>
+    where I::Item: AsRef<OsStr> {
+        MpvProcess::with_socketpair().args(args).spawn()
+    }
+
+    /// Connect to the mpv socket located at the given path, giving up after the given timeout. The
+    /// timeout is also applied to every subsequent command, see [`Mpv::set_timeout`].
+    ///
+    /// ```no_run
+    /// # use core::time::Duration;
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect_timeout("/tmp/mpvsocket", Duration::from_secs(1))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_timeout<P: AsRef<Path>>(path: P, timeout: Duration) -> Result<Self, Error> {
+        // Unix sockets have no non-blocking connect in std, so connect on a separate thread. It
+        // only lingers if mpv never accepts the connection.
+        let path = path.as_ref().to_owned();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || tx.send(UnixStream::connect(path)));
+        let sock = rx.recv_timeout(timeout).map_err(|_| Error::Timeout)?.map_err(Error::ConnectError)?;
+        let mut mpv = Self::from_stream(sock);
+        mpv.set_timeout(Some(timeout))?;
+        Ok(mpv)
+    }
+
+    /// Reconnect to mpv whenever the connection is lost, e.g. because mpv was restarted. Once the
+    /// socket at the given path accepts connections again, all properties registered with
+    /// [`Mpv::observe_property`] are observed again under their previous ids, and a synthetic
+    /// [`Event::Reconnected`] is emitted.
+    ///
+    /// Listening for events waits until mpv is back. A command interrupted by a lost connection
+    /// is sent once more after reconnecting, except for those in a [`Mpv::command_batch`], which
+    /// fail with the original error.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Event, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.enable_reconnect("/tmp/mpvsocket");
+    /// mpv.observe_property(1, "media-title")?;
+    /// loop {
+    ///     match mpv.listen_event()? {
+    ///         Event::Reconnected => println!("mpv was restarted"),
+    ///         event => println!("{event:?}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn enable_reconnect<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        self.enable_reconnect_with(move || UnixStream::connect(&path));
+    }
+}
+
+impl Mpv<TcpStream> {
+    /// Connect to mpv through a TCP socket on the local machine, e.g. a relay set up with
+    /// `socat` or a port forwarded over SSH. Addresses which are not loopback addresses are
+    /// rejected, as mpv doesn't authenticate its clients.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect_tcp("127.0.0.1:7531")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A) -> Result<Self, Error> {
+        let addrs = addr.to_socket_addrs().map_err(Error::ConnectError)?
+            .filter(|addr| addr.ip().is_loopback())
+            .collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return Err(Error::ConnectError(IoError::new(IoErrorKind::InvalidInput, "not a loopback address")));
         }
+        let sock = TcpStream::connect(addrs.as_slice()).map_err(Error::ConnectError)?;
+        sock.set_nodelay(true).map_err(Error::ConnectError)?;
+        Ok(Self::new(sock))
     }
+}
 
-    fn _command<I: Iterator<Item = Value>>(&mut self, command: I) -> Result<Value, Error> {
-        self.counter += 1;
-        let command = Value::Object({
-            let mut map = Map::with_capacity(2);
-            map.insert("command".to_owned(), command.collect());
-            map.insert("request_id".to_owned(), self.counter.into());
-            map
-        }).to_string();
-        debug!("Command: {command}");
-        self.reader.get_ref().write_all((command + "\n").as_bytes()).map_err(Error::WriteError)?;
-        loop {
-            let mut response = String::new();
-            let n = self.reader.read_line(&mut response).map_err(Error::ReadError)?;
-            if n == 0 {
-                return Err(Error::ReadError(IoErrorKind::UnexpectedEof.into()));
+impl<S: Transport> Mpv<S> {
+    /// Use an already connected transport.
+    pub fn new(transport: S) -> Self {
+        Self {
+            reader: BufReader::new(transport),
+            responses: Vec::new(),
+            counter: -1,
+            timeout: None,
+            partial: Vec::new(),
+            observed: Vec::new(),
+            reconnect: None,
+            recorder: None,
+            next_observer: FIRST_OBSERVER_ID,
+            dropped: Arc::default(),
+            hooks: Vec::new(),
+            messages: BTreeMap::new(),
+            event_filter: Vec::new(),
+            capabilities: None,
+        }
+    }
+
+    /// Set how long to wait for mpv to accept and answer a command before failing with
+    /// `Error::Timeout`. `None` waits indefinitely, which is the default. Listening for events is
+    /// not affected, use [`Mpv::listen_timeout`] for that.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.reader.get_ref().set_write_timeout(timeout).map_err(Error::WriteError)?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Read a single line, without the trailing newline. Once the deadline has passed this fails
+    /// with `Error::Timeout`, keeping any partially received line for the next call.
+    fn read_line(&mut self, deadline: Option<Instant>) -> Result<String, Error> {
+        // A zero timeout is rejected by the socket, so always wait for at least a microsecond
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()).max(Duration::from_micros(1)));
+        self.reader.get_ref().set_read_timeout(timeout).map_err(Error::ReadError)?;
+        match self.reader.read_until(b'\n', &mut self.partial) {
+            Ok(_) if self.partial.last() == Some(&b'\n') => {
+                let line = String::from_utf8(mem::take(&mut self.partial))
+                    .map_err(|e| Error::ReadError(IoError::new(IoErrorKind::InvalidData, e)))?;
+                let line = line.trim_end().to_owned();
+                if let Some(ref mut recorder) = self.recorder {
+                    recorder.record(&line, false);
+                }
+                Ok(line)
             }
-            let response = response.trim_end();
+            Ok(_) => Err(Error::ReadError(IoErrorKind::UnexpectedEof.into())),
+            Err(e) if is_timeout(&e) => Err(Error::Timeout),
+            Err(e) => Err(Error::ReadError(e)),
+        }
+    }
+
+    /// Write the given commands in one go and return the request id of the first one.
+    fn send<I: IntoIterator<Item = Value>>(&mut self, commands: I) -> Result<i64, Error> {
+        let first = self.counter + 1;
+        let mut buf = String::new();
+        for command in commands {
+            self.counter += 1;
+            let command = encode_command(command, self.counter);
+            debug!("Command: {command}");
+            if let Some(ref mut recorder) = self.recorder {
+                recorder.record(&command, true);
+            }
+            buf += command.as_str();
+            buf.push('\n');
+        }
+        self.reader.get_mut().write_all(buf.as_bytes())
+            .map_err(|e| if is_timeout(&e) { Error::Timeout } else { Error::WriteError(e) })?;
+        Ok(first)
+    }
+
+    /// Read until the next reply arrives and return it along with its request id. Events received
+    /// in the meantime are queued for `listen`.
+    fn receive(&mut self, deadline: Option<Instant>) -> Result<(i64, Map<String, Value>), Error> {
+        loop {
+            let response = self.read_line(deadline)?;
             debug!("Response: {response}");
 
             let response = response.parse::<Value>().map_err(Error::JsonError)?;
 
-            let mut map = if let Value::Object(map) = response {
+            let map = if let Value::Object(map) = response {
                 Ok(map)
             } else {
                 Err(Error::UnexpectedValue)
             }?;
 
             if let Some(Value::Number(request_id)) = map.get("request_id") {
-                if request_id.as_i64() != Some(self.counter) {
-                    continue;
+                if let Some(request_id) = request_id.as_i64() {
+                    return Ok((request_id, map));
                 }
-            } else {
-                if let Some(Value::String(_)) = map.get("event") {
-                    self.responses.push(map);
+            } else if let Some(Value::String(_)) = map.get("event") {
+                self.responses.push(map);
+            }
+        }
+    }
+
+    fn try_command(&mut self, command: Value, deadline: Option<Instant>) -> Result<Value, Error> {
+        let request_id = self.send(once(command.clone()))?;
+        loop {
+            let (id, map) = self.receive(deadline)?;
+            if id == request_id {
+                return reply_value(map, &command);
+            }
+        }
+    }
+
+    fn _command(&mut self, command: Value) -> Result<Value, Error> {
+        self.unobserve_dropped();
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        if self.reconnect.is_none() {
+            return self.try_command(command, deadline);
+        }
+        match self.try_command(command.clone(), deadline) {
+            Err(e) if is_disconnect(&e) => {
+                self.reconnect(deadline)?;
+                self.try_command(command, deadline)
+            }
+            result => result,
+        }
+    }
+
+    /// Write every request sent to mpv and every reply and event received from it to the given
+    /// writer, as an NDJSON transcript with one timestamped message per line. With the `testing`
+    /// feature, a transcript can be served back by `testing::Replay`.
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.record(File::create("transcript.ndjson").map_err(Error::WriteError)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn record<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.recorder = Some(Recorder::new(writer));
+    }
+
+    /// Stop recording, see [`Mpv::record`].
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Reconnect whenever the connection is lost, opening a new transport with the given function.
+    /// See [`Mpv::enable_reconnect`].
+    pub fn enable_reconnect_with<F: FnMut() -> IoResult<S> + Send + 'static>(&mut self, connect: F) {
+        self.reconnect = Some(Box::new(connect));
+    }
+
+    /// Stop reconnecting to mpv, see [`Mpv::enable_reconnect`].
+    pub fn disable_reconnect(&mut self) {
+        self.reconnect = None;
+    }
+
+    /// Reconnect and restore the state of the previous connection.
+    fn reconnect(&mut self, deadline: Option<Instant>) -> Result<(), Error> {
+        const INTERVAL: Duration = Duration::from_millis(100);
+
+        loop {
+            let Some(ref mut connect) = self.reconnect else {
+                return Err(Error::ConnectError(IoErrorKind::NotConnected.into()));
+            };
+            match connect() {
+                Ok(sock) => {
+                    debug!("Reconnected");
+                    sock.set_write_timeout(self.timeout).map_err(Error::WriteError)?;
+                    self.reader = BufReader::new(sock);
+                    self.partial.clear();
+                    // The player may have been replaced by another version
+                    self.capabilities = None;
+                }
+                Err(e) => {
+                    debug!("Reconnect failed: {e}");
+                    if let Some(deadline) = deadline && Instant::now() >= deadline {
+                        return Err(Error::Timeout);
+                    }
+                    thread::sleep(INTERVAL);
+                    continue;
                 }
-                continue;
             }
 
-            let error = if let Some(Value::String(error)) = map.get("error") {
-                Ok(error)
-            } else {
-                Err(Error::UnexpectedValue)
-            }?;
+            // Queue the event first, so that it precedes the changes caused by observing again
+            let queued = self.responses.len();
+            self.responses.push(Map::from_iter([("event".to_owned(), "reconnected".into())]));
+            let observed = self.observed.clone();
+            let hooks = self.hooks.iter().zip(FIRST_HOOK_ID..)
+                .map(|(hook, id)| Value::Array(vec!["hook_add".into(), hook.name.as_str().into(), id.into(), hook.priority.into()]))
+                .collect::<Vec<_>>();
+            let events = self.event_filter.iter()
+                .map(|&(ref event, enable)| Value::Array(vec![if enable { "enable_event" } else { "disable_event" }.into(), event.as_str().into()]))
+                .collect::<Vec<_>>();
+            let result = events.into_iter()
+                .chain(observed.into_iter().map(|(id, property)| Value::Array(vec!["observe_property".into(), id.into(), property.into()])))
+                .chain(hooks)
+                .try_for_each(|command| self.try_command(command, deadline).map(|_| ()));
+            match result {
+                Err(e) if is_disconnect(&e) => self.responses.truncate(queued),
+                result => return result,
+            }
+        }
+    }
 
-            return if error == "success" {
-                if map.contains_key("data") {
-                    Ok(map["data"].take())
-                } else {
-                    Ok(Value::Null)
+    /// Run several mpv commands without waiting for each reply in between. Each command is a JSON
+    /// array holding the command name followed by its arguments. The result of every command is
+    /// returned in order; the outer error is only set if the connection itself failed.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # use serde_json::json;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// let results = mpv.command_batch([
+    ///     json!(["playlist-move", 3, 0]),
+    ///     json!(["get_property", "playlist-count"]),
+    /// ])?;
+    /// for result in results {
+    ///     println!("{}", result?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn command_batch<I: IntoIterator<Item = Value>>(&mut self, commands: I) -> Result<Vec<Result<Value, Error>>, Error> {
+        // Keep the amount of unread replies bounded, mpv stops reading commands once it can't
+        // write its replies anymore
+        const WINDOW: usize = 128;
+
+        self.unobserve_dropped();
+        let mut commands = commands.into_iter().peekable();
+        let mut results = Vec::new();
+        while commands.peek().is_some() {
+            let deadline = self.timeout.map(|t| Instant::now() + t);
+            match self.try_batch(commands.by_ref().take(WINDOW), deadline) {
+                Ok(window) => results.extend(window),
+                Err(e) => {
+                    if is_disconnect(&e) && self.reconnect.is_some() {
+                        let _ = self.reconnect(deadline);
+                    }
+                    return Err(e);
                 }
-            } else {
-                Err(Error::MpvError(error.to_owned()))
-            };
+            }
+        }
+        Ok(results)
+    }
+
+    fn try_batch<I: Iterator<Item = Value>>(&mut self, commands: I, deadline: Option<Instant>) -> Result<Vec<Result<Value, Error>>, Error> {
+        let commands = commands.collect::<Vec<_>>();
+        let first = self.send(commands.iter().cloned())?;
+        let mut window = (first..=self.counter).map(|_| None).collect::<Vec<_>>();
+        let mut remaining = window.len();
+        while remaining > 0 {
+            let (id, map) = self.receive(deadline)?;
+            if let Ok(i) = usize::try_from(id - first)
+                    && let Some(slot @ None) = window.get_mut(i) {
+                *slot = Some(reply_value(map, &commands[i]));
+                remaining -= 1;
+            }
         }
+        Ok(window.into_iter().flatten().collect())
     }
 
     /// Run an mpv command. The arguments are passed as a collection whose elements can be
     /// converted to JSON values.
     ///
-    /// ```
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// mpv.command_arg("seek", ["0", "absolute"])?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn command_arg<I: IntoIterator>(&mut self, command: &str, args: I) -> Result<(), Error>
     where I::Item: Into<Value> {
-        // XXX: Drop return value for now, change interface later if needed
-        self._command(once(command.into()).chain(args.into_iter().map(Into::into))).map(|_| ())
+        self.command_value(command, args).map(|_| ())
     }
 
-    /// Run an mpv command without any arguments.
+    /// Run an mpv command and return its result. Commands which don't produce a result return
+    /// `Value::Null`.
     ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// let path = mpv.command_value("expand-path", ["~~/watch_later"])?;
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn command_value<I: IntoIterator>(&mut self, command: &str, args: I) -> Result<Value, Error>
+    where I::Item: Into<Value> {
+        self._command(once(command.into()).chain(args.into_iter().map(Into::into)).collect())
+    }
+
+    /// Run an mpv command without any arguments.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// mpv.command("playlist-shuffle")?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn command(&mut self, command: &str) -> Result<(), Error> {
-        self._command(once(command.into())).map(|_| ())
+        self._command(Value::Array(vec![command.into()])).map(|_| ())
     }
 
-    /// Retrieve a property from mpv.
+    /// Run a typed mpv command and return its result. Commands which don't produce a result
+    /// return `Value::Null`. Fails with [`Error::UnexpectedValue`] without sending anything if a
+    /// number in the command is infinite or NaN.
     ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv, MpvCommand};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.execute(&MpvCommand::PlaylistMove { from: 3, to: 0 })?;
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn execute(&mut self, command: &MpvCommand) -> Result<Value, Error> {
+        self._command(command.try_into()?)
+    }
+
+    /// Retrieve a property from mpv.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// let paused = mpv.get_property("pause")?.as_bool().ok_or(Error::UnexpectedValue)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn get_property(&mut self, property: &str) -> Result<Value, Error> {
-        self._command(["get_property".into(), property.into()].into_iter())
+        self._command(Value::Array(vec!["get_property".into(), property.into()]))
     }
 
-    /// Set an mpv property to the given value.
+    /// Retrieve a property from mpv and deserialize it into the given type.
     ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv, PlaylistEntry};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// let count = mpv.get_property_as::<u64>("playlist-count")?;
+    /// let playlist = mpv.get_property_as::<Vec<PlaylistEntry>>("playlist")?;
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn get_property_as<T: DeserializeOwned>(&mut self, property: &str) -> Result<T, Error> {
+        serde_json::from_value(self.get_property(property)?).map_err(Error::JsonError)
+    }
+
+    /// Set an mpv property to the given value.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// mpv.set_property("pause", true)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn set_property<T: Into<Value>>(&mut self, property: &str, value: T) -> Result<(), Error> {
-        self._command(["set_property".into(), property.into(), value.into()].into_iter()).map(|_| ())
+        self._command(Value::Array(vec!["set_property".into(), property.into(), value.into()])).map(|_| ())
     }
 
     /// Add the given value to an mpv property. Runs the 'add' mpv command.
     ///
-    /// ```
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// mpv.add_property("volume", 20.0)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn add_property(&mut self, property: &str, value: f64) -> Result<(), Error> {
-        self._command(["add".into(), property.into(),
-            Number::from_f64(value).ok_or(Error::UnexpectedValue)?.into()].into_iter()).map(|_| ())
+        self._command(Value::Array(vec!["add".into(), property.into(), number(value)?])).map(|_| ())
     }
 
     /// Multiply an mpv property by the given value. Runs the 'multiply' mpv command.
     ///
-    /// ```
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// mpv.multiply_property("speed", 2.0)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn multiply_property(&mut self, property: &str, value: f64) -> Result<(), Error> {
-        self._command(["multiply".into(), property.into(),
-            Number::from_f64(value).ok_or(Error::UnexpectedValue)?.into()].into_iter()).map(|_| ())
+        self._command(Value::Array(vec!["multiply".into(), property.into(), number(value)?])).map(|_| ())
     }
 
     /// Watch a property for changes. Runs the `observe_property` mpv command.
     pub fn observe_property(&mut self, id: isize, property: &str) -> Result<(), Error> {
-        self._command(["observe_property".into(), id.into(), property.into()].into_iter()).map(|_| ())
+        self._command(Value::Array(vec!["observe_property".into(), id.into(), property.into()]))?;
+        self.observed.push((id, property.to_owned()));
+        Ok(())
     }
 
     /// Undo the corresponding `observe_property`. Runs the `unobserve_property` mpv command.
     pub fn unobserve_property(&mut self, id: isize) -> Result<(), Error> {
-        self._command(["unobserve_property".into(), id.into()].into_iter()).map(|_| ())
+        self.observed.retain(|&(i, _)| i != id);
+        self._command(Value::Array(vec!["unobserve_property".into(), id.into()])).map(|_| ())
     }
 
-    /// Block until an mpv event occurs and return the event.
+    /// Watch a property for changes under a newly allocated id. The property is unobserved once
+    /// the returned [`Observer`] is dropped, so components sharing a connection don't have to
+    /// coordinate their ids.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// let volume = mpv.observe("volume")?;
+    /// loop {
+    ///     println!("volume: {}", mpv.listen_for(&volume)?);
+    /// }
+    /// # }
+    /// ```
+    pub fn observe(&mut self, property: &str) -> Result<Observer, Error> {
+        let id = self.next_observer;
+        self.observe_property(id, property)?;
+        self.next_observer += 1;
+        Ok(Observer::new(id, property, &self.dropped))
+    }
+
+    /// Unobserve the properties of dropped observers, and discard their queued changes.
+    fn unobserve_dropped(&mut self) {
+        let dropped = mem::take(&mut *self.dropped.lock().unwrap_or_else(|e| e.into_inner()));
+        for id in dropped {
+            let _ = self.unobserve_property(id);
+            self.responses.retain(|event| !is_change_of(event, id));
+        }
+    }
+
+    /// Block until the property of the given observer changes and return its new value, which is
+    /// `Value::Null` if the property is unavailable. Other events received in the meantime are
+    /// kept for `listen`.
+    pub fn listen_for(&mut self, observer: &Observer) -> Result<Value, Error> {
+        let id = observer.id();
+        let mut event = self.next_event_matching(None, |event| is_change_of(event, id))?;
+        Ok(event.remove("data").unwrap_or(Value::Null))
+    }
+
+    /// Register a callback for an mpv hook, e.g. `on_load`, `on_preloaded`, `on_unload` or
+    /// `on_before_start_file`. mpv waits while the callback runs, which makes it possible to
+    /// change the file or its options before playback starts. Hooks with a lower priority run
+    /// first; mpv's default is 0. Returns the id the hook is registered under.
+    ///
+    /// The callback is run by any of the `listen` methods and `poll_event` when the hook is
+    /// reached, and mpv is told to continue once it returns; the hook event itself isn't
+    /// returned. Hooks are registered again after reconnecting.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.hook_add("on_load", 0, |mpv| {
+    ///     let url = mpv.get_property_as::<String>("stream-open-filename")?;
+    ///     if let Some(id) = url.strip_prefix("yt:") {
+    ///         mpv.set_property("stream-open-filename", format!("https://youtu.be/{id}"))?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// loop {
+    ///     mpv.listen_event()?;
+    /// }
+    /// # }
+    /// ```
+    pub fn hook_add<F: FnMut(&mut Self) -> Result<(), Error> + Send + 'static>(&mut self, name: &str, priority: i64, callback: F) -> Result<u64, Error> {
+        let id = FIRST_HOOK_ID + self.hooks.len() as u64;
+        self._command(Value::Array(vec!["hook_add".into(), name.into(), id.into(), priority.into()]))?;
+        self.hooks.push(Hook { name: name.to_owned(), priority, callback: Some(Box::new(callback)) });
+        Ok(id)
+    }
+
+    /// Send a message to all scripts and clients, which receive it as a `client-message` event.
+    /// By convention, the first argument names the message.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.script_message(["osc-visibility", "always"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn script_message<I: IntoIterator>(&mut self, args: I) -> Result<(), Error>
+    where I::Item: Into<String> {
+        self.execute(&MpvCommand::ScriptMessage(args.into_iter().map(Into::into).collect())).map(|_| ())
+    }
+
+    /// Send a message to the script or client with the given name, see [`Mpv::client_name`].
+    pub fn script_message_to<I: IntoIterator>(&mut self, target: &str, args: I) -> Result<(), Error>
+    where I::Item: Into<String> {
+        let args = args.into_iter().map(Into::into).collect();
+        self.execute(&MpvCommand::ScriptMessageTo { target: target.to_owned(), args }).map(|_| ())
+    }
+
+    /// The name mpv knows this connection by, e.g. `ipc-3`. Scripts can send messages to it with
+    /// `script-message-to`.
+    pub fn client_name(&mut self) -> Result<String, Error> {
+        match self._command(Value::Array(vec!["client_name".into()]))? {
+            Value::String(name) => Ok(name),
+            _ => Err(Error::UnexpectedValue),
+        }
+    }
+
+    /// Call the given function with the remaining arguments whenever a `client-message` event
+    /// whose first argument is `name` is received, replacing any previous handler for it.
+    ///
+    /// Like hooks, handlers are run by any of the `listen` methods and `poll_event`, and the
+    /// handled events aren't returned.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// // Bound in input.conf with `ctrl+l script-message like`
+    /// mpv.on_message("like", |mpv, _| {
+    ///     let title = mpv.get_property_as::<String>("media-title")?;
+    ///     println!("Liked {title}");
+    ///     Ok(())
+    /// });
+    /// loop {
+    ///     mpv.listen_event()?;
+    /// }
+    /// # }
+    /// ```
+    pub fn on_message<F: FnMut(&mut Self, &[String]) -> Result<(), Error> + Send + 'static>(&mut self, name: &str, callback: F) {
+        self.messages.insert(name.to_owned(), Some(Box::new(callback)));
+    }
+
+    /// What the connected mpv supports. This is queried on first use and kept until the connection
+    /// is re-established.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// if mpv.capabilities()?.supports_command("playlist-play-index") {
+    ///     mpv.command_arg("playlist-play-index", [2])?;
+    /// } else {
+    ///     mpv.set_property("playlist-pos", 2)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capabilities(&mut self) -> Result<&Capabilities, Error> {
+        let capabilities = match self.capabilities.take() {
+            Some(capabilities) => capabilities,
+            None => Capabilities::query(self)?,
+        };
+        Ok(self.capabilities.insert(capabilities))
+    }
+
+    /// Use the given capabilities instead of querying them from mpv, until the connection is
+    /// re-established. Useful when there is no real mpv to ask, e.g. with [`DryRun`].
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = Some(capabilities);
+    }
+
+    /// Receive the events with the given name again after [`Mpv::disable_event`], or all events
+    /// if `name` is `"all"`. All events are enabled initially.
+    pub fn enable_event(&mut self, name: &str) -> Result<(), Error> {
+        self.filter_event(name, true)
+    }
+
+    /// Stop receiving the events with the given name, or any events if `name` is `"all"`. This
+    /// includes `property-change`, `log-message` and `client-message` events. Events of the
+    /// given name which were already received are discarded.
     ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.disable_event("all")?;
+    /// mpv.enable_event("end-file")?;
+    /// loop {
+    ///     println!("{:?}", mpv.listen_event()?);
+    /// }
+    /// # }
     /// ```
+    pub fn disable_event(&mut self, name: &str) -> Result<(), Error> {
+        self.filter_event(name, false)?;
+        self.responses.retain(|event| name != "all" && event.get("event").and_then(Value::as_str) != Some(name));
+        Ok(())
+    }
+
+    fn filter_event(&mut self, name: &str, enable: bool) -> Result<(), Error> {
+        let command = if enable { "enable_event" } else { "disable_event" };
+        self._command(Value::Array(vec![command.into(), name.into()]))?;
+        // Only the last setting for an event matters, and "all" overrides every previous one
+        if name == "all" {
+            self.event_filter.clear();
+        } else {
+            self.event_filter.retain(|(event, _)| event != name);
+        }
+        self.event_filter.push((name.to_owned(), enable));
+        Ok(())
+    }
+
+    /// Receive mpv's log messages of the given level or more severe as [`Event::LogMessage`]
+    /// events, or stop receiving them if `None`.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, LogLevel, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.request_log_messages(Some(LogLevel::Warn))?;
+    /// loop {
+    ///     let message = mpv.listen_log()?;
+    ///     eprint!("[{}] {}", message.prefix, message.text);
+    /// }
+    /// # }
+    /// ```
+    pub fn request_log_messages(&mut self, level: Option<LogLevel>) -> Result<(), Error> {
+        let level = level.map_or("no", LogLevel::as_str);
+        self._command(Value::Array(vec!["request_log_messages".into(), level.into()])).map(|_| ())
+    }
+
+    /// Block until a log message is received and return it. Other events received in the
+    /// meantime are kept for `listen`.
+    pub fn listen_log(&mut self) -> Result<LogMessage, Error> {
+        let event = self.next_event_matching(None, |event| event.get("event").and_then(Value::as_str) == Some("log-message"))?;
+        serde_json::from_value(Value::Object(event)).map_err(Error::JsonError)
+    }
+
+    /// Let mpv continue after a hook. Only needed for hooks registered by hand with the
+    /// `hook_add` command, using the `id` of their [`Event::Hook`].
+    pub fn hook_ack(&mut self, id: i64) -> Result<(), Error> {
+        self._command(Value::Array(vec!["hook_ack".into(), id.into()])).map(|_| ())
+    }
+
+    /// Block until an mpv event occurs and return the event.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// loop {
     ///     let event = mpv.listen()?;
     ///     println!("{:?}", event);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn listen(&mut self) -> Result<Map<String, Value>, Error> {
-        if !self.responses.is_empty() {
-            return Ok(self.responses.remove(0));
+        self.next_event(None)
+    }
+
+    fn next_event(&mut self, deadline: Option<Instant>) -> Result<Map<String, Value>, Error> {
+        self.next_event_matching(deadline, |_| true)
+    }
+
+    /// Run the hook callback or message handler an event belongs to. Returns whether there was
+    /// one, in which case the event is consumed.
+    fn run_handler(&mut self, event: &Map<String, Value>) -> Result<bool, Error> {
+        match event.get("event").and_then(Value::as_str) {
+            Some("hook") => self.run_hook(event),
+            Some("client-message") => self.run_message_handler(event),
+            _ => Ok(false),
+        }
+    }
+
+    /// Run the handlers of the queued events, which were received while waiting for a reply.
+    fn run_queued_handlers(&mut self) -> Result<(), Error> {
+        let mut i = 0;
+        while i < self.responses.len() {
+            if !matches!(self.responses[i].get("event").and_then(Value::as_str), Some("hook" | "client-message")) {
+                i += 1;
+                continue;
+            }
+            let event = self.responses.remove(i);
+            if self.run_handler(&event)? {
+                // The handler may have consumed or queued events itself, so start over
+                i = 0;
+            } else {
+                self.responses.insert(i, event);
+                i += 1;
+            }
         }
+        Ok(())
+    }
+
+    /// Run the callback of a hook registered with [`Mpv::hook_add`] and acknowledge it. Returns
+    /// whether the event belonged to such a hook.
+    fn run_hook(&mut self, event: &Map<String, Value>) -> Result<bool, Error> {
+        let index = event.get("hook_id").and_then(Value::as_u64)
+            .and_then(|id| usize::try_from(id.checked_sub(FIRST_HOOK_ID)?).ok())
+            .filter(|&i| i < self.hooks.len());
+        let (Some(index), Some(id)) = (index, event.get("id").and_then(Value::as_i64)) else {
+            return Ok(false);
+        };
+        let result = if let Some(mut callback) = self.hooks[index].callback.take() {
+            let result = callback(self);
+            self.hooks[index].callback = Some(callback);
+            result
+        } else {
+            Ok(())
+        };
+        // mpv waits for the acknowledgement even if the callback failed
+        self.hook_ack(id)?;
+        result.map(|()| true)
+    }
+
+    /// Run the handler registered with [`Mpv::on_message`] for a `client-message` event. Returns
+    /// whether there was one.
+    fn run_message_handler(&mut self, event: &Map<String, Value>) -> Result<bool, Error> {
+        let Some(args) = event.get("args").and_then(|args| serde_json::from_value::<Vec<String>>(args.clone()).ok()) else {
+            return Ok(false);
+        };
+        let Some(name) = args.first() else {
+            return Ok(false);
+        };
+        let Some(slot) = self.messages.get_mut(name) else {
+            return Ok(false);
+        };
+        if let Some(mut callback) = slot.take() {
+            let result = callback(self, &args[1..]);
+            // Keep a handler replaced by the callback itself
+            self.messages.entry(args[0].clone()).or_default().get_or_insert(callback);
+            result?;
+        }
+        Ok(true)
+    }
+
+    /// Return the first event which satisfies the predicate, queueing all others. Events which
+    /// belong to a hook callback or message handler are passed to it instead, whether or not
+    /// they satisfy the predicate, as mpv waits for hooks to be acknowledged.
+    fn next_event_matching<F: Fn(&Map<String, Value>) -> bool>(&mut self, deadline: Option<Instant>, predicate: F) -> Result<Map<String, Value>, Error> {
+        self.unobserve_dropped();
+        self.run_queued_handlers()?;
         loop {
-            let response = self.listen_raw()?;
+            if let Some(i) = self.responses.iter().position(&predicate) {
+                return Ok(self.responses.remove(i));
+            }
+            let response = match self.read_line(deadline) {
+                Err(e) if is_disconnect(&e) && self.reconnect.is_some() => {
+                    self.reconnect(deadline)?;
+                    continue;
+                }
+                response => response?,
+            };
             debug!("Event: {response}");
 
             let event = response.parse::<Value>().map_err(Error::JsonError)?;
 
             if let Value::Object(map) = event {
                 if let Some(Value::String(_)) = map.get("event") {
-                    return Ok(map);
+                    if self.run_handler(&map)? {
+                        self.run_queued_handlers()?;
+                    } else if predicate(&map) {
+                        return Ok(map);
+                    } else {
+                        self.responses.push(map);
+                    }
+                } else {
+                    debug!("Bad response: {response:?}");
                 }
-                debug!("Bad response: {response:?}");
             } else {
                 return Err(Error::UnexpectedValue);
             }
         }
     }
 
-    /// Block until an mpv event occurs and return the event as a string.
+    /// Block until an mpv event occurs and return it as a typed [`Event`].
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Event, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// mpv.observe_property(1, "pause")?;
+    /// loop {
+    ///     if let Event::PropertyChange { name, data, .. } = mpv.listen_event()? {
+    ///         println!("{name}: {data}");
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn listen_event(&mut self) -> Result<Event, Error> {
+        serde_json::from_value(Value::Object(self.listen()?)).map_err(Error::JsonError)
+    }
+
+    /// Wait up to the given duration for an mpv event. Returns `None` if no event arrived in time.
     ///
+    /// ```no_run
+    /// # use core::time::Duration;
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+    /// while let Some(event) = mpv.listen_timeout(Duration::from_secs(5))? {
+    ///     println!("{event:?}");
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn listen_timeout(&mut self, timeout: Duration) -> Result<Option<Event>, Error> {
+        match self.next_event(Some(Instant::now() + timeout)) {
+            Ok(map) => serde_json::from_value(Value::Object(map)).map(Some).map_err(Error::JsonError),
+            Err(Error::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Return an event if one is immediately available, without blocking.
+    pub fn poll_event(&mut self) -> Result<Option<Event>, Error> {
+        self.listen_timeout(Duration::ZERO)
+    }
+
+    /// Block until an mpv event occurs and return the event as a string. Like with `listen`,
+    /// events received while waiting for a reply are returned first, and events handled by a
+    /// hook callback or message handler aren't returned.
+    ///
+    /// ```no_run
+    /// # use mpvc::{Error, Mpv};
+    /// # fn main() -> Result<(), Error> {
     /// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
     /// while let Ok(response) = mpv.listen_raw() {
     ///     println!("{}", response);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn listen_raw(&mut self) -> Result<String, Error> {
-        let mut response = String::new();
-        let n = self.reader.read_line(&mut response).map_err(Error::ReadError)?;
-        if n == 0 {
-            return Err(Error::ReadError(IoErrorKind::UnexpectedEof.into()));
+        loop {
+            self.run_queued_handlers()?;
+            if !self.responses.is_empty() {
+                return Ok(Value::Object(self.responses.remove(0)).to_string());
+            }
+            let response = match self.read_line(None) {
+                Err(e) if is_disconnect(&e) && self.reconnect.is_some() => {
+                    // The queued reconnected event is returned next
+                    self.reconnect(None)?;
+                    continue;
+                }
+                response => response?,
+            };
+            if let Ok(Value::Object(event)) = response.parse::<Value>() && self.run_handler(&event)? {
+                continue;
+            }
+            return Ok(response);
         }
-        Ok(response.trim_end().to_owned())
     }
 }
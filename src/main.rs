@@ -1,15 +1,16 @@
 use core::iter::Iterator;
 use core::time::Duration;
+use std::fs::File;
 use std::io;
-use std::process::Command as Cmd;
-use std::thread;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-use mpvc::{Error, Mpv};
+use mpvc::{Capabilities, DryRun, Error, Event, LoadMode, LogLevel, Metadata, Mpv, MpvErrorKind, MpvProcess, Player, Seek, Transport};
 
-use clap::{Arg, ArgAction, Command, ValueHint, builder::EnumValueParser};
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueHint, builder::EnumValueParser};
 use clap_complete::Shell;
 use colored::Colorize as _;
-use serde_json::{Map, Value};
+use serde_json::{Map, Value, json};
 
 fn value_to_string(v: &Value) -> Result<String, Error> {
     match *v {
@@ -21,9 +22,155 @@ fn value_to_string(v: &Value) -> Result<String, Error> {
     }
 }
 
-fn main() -> Result<(), Error> {
-    let mut cli = Command::new(env!("CARGO_CRATE_NAME"))
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let secs = s.parse::<f64>().map_err(|e| e.to_string())?;
+    let duration = Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())?;
+    if duration.is_zero() {
+        return Err("must be greater than zero".to_owned());
+    }
+    Ok(duration)
+}
+
+/// Parse a position in seconds of the form `[-][[hh:]mm:]ss`.
+fn parse_position(s: &str) -> Result<f64, String> {
+    let (sign, s) = s.strip_prefix('-').map_or((1.0, s), |s| (-1.0, s));
+    let mut secs = 0.0;
+    for (i, part) in s.split(':').enumerate() {
+        if i > 2 {
+            return Err("expected [[hh:]mm:]ss".to_owned());
+        }
+        secs = secs * 60.0 + part.parse::<f64>().map_err(|e| e.to_string())?;
+    }
+    // mpv can't be sent infinity or NaN, as JSON has no representation for them
+    if !secs.is_finite() {
+        return Err("must be a finite number".to_owned());
+    }
+    Ok(sign * secs)
+}
+
+fn parse_object(s: &str) -> Result<Map<String, Value>, String> {
+    s.parse::<Value>().map_err(|e| e.to_string())?
+        .as_object().cloned().ok_or_else(|| "expected a JSON object".to_owned())
+}
+
+/// Why mpvc failed, with the context needed to explain it.
+enum Failure {
+    Mpv(Error),
+    /// An argument clap accepted, which turned out to be invalid, e.g. malformed JSON.
+    Usage(String),
+    /// A property or metadata key which has no value.
+    Missing(String),
+    /// The transcript for `--record` couldn't be created.
+    Record(PathBuf, io::Error),
+    /// A command the connected mpv doesn't have, along with its version.
+    Unsupported(String, Option<String>),
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        Self::Mpv(e)
+    }
+}
+
+impl Failure {
+    /// The exit status, see `EXIT_STATUS`.
+    fn exit_code(&self) -> u8 {
+        match *self {
+            Self::Mpv(Error::ConnectError(_) | Error::SpawnError(_)) => 3,
+            Self::Mpv(Error::Timeout) => 4,
+            Self::Mpv(Error::MpvError(ref e)) => match e.kind {
+                MpvErrorKind::PropertyUnavailable => 6,
+                MpvErrorKind::PropertyNotFound => 7,
+                MpvErrorKind::InvalidParameter | MpvErrorKind::PropertyFormat => 8,
+                _ => 5,
+            },
+            Self::Usage(_) => 2,
+            Self::Unsupported(..) => 8,
+            Self::Mpv(Error::MissingValue) | Self::Missing(_) => 6,
+            Self::Mpv(_) | Self::Record(..) => 1,
+        }
+    }
+
+    fn report(&self, socket: &str) {
+        let name = env!("CARGO_CRATE_NAME");
+        match *self {
+            Self::Mpv(Error::ConnectError(ref e)) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => {
+                eprintln!("{name}: no mpv is listening on {socket}");
+                eprintln!("Start mpv with `--input-ipc-server={socket}`, or add files with `{name} add` to start one.");
+            }
+            Self::Mpv(Error::ConnectError(ref e)) => eprintln!("{name}: cannot connect to {socket}: {e}"),
+            Self::Mpv(Error::SpawnError(ref e)) => eprintln!("{name}: cannot start mpv: {e}"),
+            Self::Mpv(Error::Timeout) => eprintln!("{name}: mpv didn't respond in time"),
+            Self::Mpv(Error::ReadError(ref e) | Error::WriteError(ref e)) => eprintln!("{name}: lost the connection to mpv: {e}"),
+            Self::Mpv(Error::JsonError(ref e)) => eprintln!("{name}: invalid JSON: {e}"),
+            Self::Mpv(Error::MpvError(ref e)) => match (e.kind, e.property()) {
+                (MpvErrorKind::PropertyUnavailable, Some(property)) if let Some(key) = property.strip_prefix("metadata/by-key/") => {
+                    eprintln!("{name}: the current file has no metadata key '{key}'");
+                }
+                (MpvErrorKind::PropertyUnavailable, Some(property)) => eprintln!("{name}: property '{property}' is unavailable"),
+                (MpvErrorKind::PropertyNotFound, Some(property)) => eprintln!("{name}: no such property '{property}'"),
+                _ => eprintln!("{name}: mpv failed to run {}: {}", e.command, e.message),
+            },
+            Self::Mpv(ref e) => eprintln!("{name}: {e}"),
+            Self::Usage(ref message) => eprintln!("{name}: {message}"),
+            Self::Missing(ref what) => eprintln!("{name}: {what} has no value"),
+            Self::Record(ref path, ref e) => eprintln!("{name}: cannot create {}: {e}", path.display()),
+            Self::Unsupported(ref command, ref version) => {
+                eprintln!("{name}: {} doesn't support the command '{command}'", version.as_deref().unwrap_or("the running mpv"));
+                eprintln!("A newer version of mpv is needed for this.");
+            }
+        }
+    }
+}
+
+const EXIT_STATUS: &str = "\
+Exit status:
+  0  Success
+  1  Any other error
+  2  Invalid usage
+  3  mpv isn't running, or couldn't be connected to or started
+  4  mpv didn't respond within the timeout
+  5  mpv failed to run a command
+  6  A property or metadata key has no value
+  7  No such property
+  8  Invalid command or argument, or a command the running mpv doesn't support";
+
+/// Parse a JSON argument, which is a usage error if it's malformed.
+fn parse_json(s: &str) -> Result<Value, Failure> {
+    s.parse().map_err(|e| Failure::Usage(format!("invalid JSON '{s}': {e}")))
+}
+
+/// Explain an error about an invalid command if the connected mpv doesn't have the command at
+/// all, which happens with older versions.
+fn explain_unsupported<S: Transport>(mpv: &mut Mpv<S>, failure: Failure) -> Failure {
+    if let Failure::Mpv(Error::MpvError(ref e)) = failure
+            && e.kind == MpvErrorKind::InvalidParameter
+            && let Some(command) = e.command.get(0).or_else(|| e.command.get("name")).and_then(Value::as_str)
+            && let Ok(capabilities) = mpv.capabilities()
+            && !capabilities.supports_command(command) {
+        return Failure::Unsupported(command.to_owned(), capabilities.version_string().map(str::to_owned));
+    }
+    failure
+}
+
+fn main() -> ExitCode {
+    let mut cli = cli();
+    let matches = cli.get_matches_mut();
+    match try_main(&mut cli, &matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if !matches.get_flag("quiet") {
+                e.report(matches.get_one::<String>("socket").unwrap());
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn cli() -> Command {
+    Command::new(env!("CARGO_CRATE_NAME"))
         .about("An mpc-like CLI tool for mpv")
+        .after_help(EXIT_STATUS)
         .subcommand_required(true)
         .arg_required_else_help(true)
         .arg(Arg::new("socket")
@@ -32,6 +179,34 @@ fn main() -> Result<(), Error> {
             .value_name("/path/to/socket")
             .value_hint(ValueHint::AnyPath)
             .default_value("/tmp/mpv.sock"))
+        .arg(Arg::new("quiet")
+            .help("Don't print error messages, only exit with the status")
+            .short('q')
+            .long("quiet")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("timeout")
+            .help("Give up if mpv doesn't respond within the given amount of seconds")
+            .short('T')
+            .long("timeout")
+            .value_name("seconds")
+            .value_parser(parse_duration))
+        .arg(Arg::new("record")
+            .help("Write all messages exchanged with mpv to the given file as an NDJSON transcript")
+            .long("record")
+            .value_name("file")
+            .value_hint(ValueHint::FilePath)
+            .value_parser(clap::value_parser!(PathBuf)))
+        .arg(Arg::new("dry-run")
+            .help("Print the JSON commands which would be sent to mpv instead of connecting to it. \
+                Queries are answered from a stub state, whose properties may be given as a JSON object; \
+                output based on these answers is printed between the commands as usual. \
+                Commands which wait for events stop once their setup is printed.")
+            .long("dry-run")
+            .value_name("state")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("{}")
+            .value_parser(parse_object))
         .subcommand(Command::new("play")
             .about("Resume playback"))
         .subcommand(Command::new("pause")
@@ -46,6 +221,9 @@ fn main() -> Result<(), Error> {
             .about("Set the playback position. By default seeks by a relative amount of seconds. \
                    You may use negative values.")
             .arg(Arg::new("target")
+                .value_name("[-][[hh:]mm:]ss")
+                .allow_hyphen_values(true)
+                .value_parser(parse_position)
                 .required(true))
             .arg(Arg::new("mode")
                 .help("relative: Seek relative to the current position (a negative value seeks backwards)\n\
@@ -72,7 +250,8 @@ fn main() -> Result<(), Error> {
                     append: Append the file to the playlist\n\
                     append-play: Append the file and if nothing is currently playing, start playback\n\
                     insert-next: Insert the file into the playlist directly after the current entry\n\
-                    insert-next-play: Insert the file into the next position and if nothing is currently playing, start playback\n")
+                    insert-next-play: Insert the file into the next position and if nothing is currently playing, start playback\n\
+                    append-next: Deprecated, same as insert-next\n")
                 .short('m')
                 .long("mode")
                 .value_parser(["replace", "append", "append-play", "append-next", "insert-next", "insert-next-play"])
@@ -169,14 +348,20 @@ fn main() -> Result<(), Error> {
             .arg(Arg::new("property")
                 .required(true)))
         .subcommand(Command::new("run")
-            .about("Run an mpv command")
+            .about("Run an mpv command and print its result, if any")
+            .arg(Arg::new("json")
+                .help("Print the result as JSON")
+                .short('j')
+                .long("json")
+                .action(ArgAction::SetTrue))
             .arg(Arg::new("command")
                 .required(true))
             .arg(Arg::new("args")
                 .help("String arguments")
                 .num_args(0..))
-            .arg(Arg::new("json")
+            .arg(Arg::new("json-args")
                 .help("JSON arguments")
+                .value_name("json")
                 .num_args(1..)
                 .last(true)))
         .subcommand(Command::new("metadata")
@@ -219,6 +404,13 @@ fn main() -> Result<(), Error> {
                 .required(true)))
         .subcommand(Command::new("observe")
             .about("Print all mpv events in real-time. Additionally, observe a set of properties and inform about changes.")
+            .arg(Arg::new("events")
+                .help("Only print the given events, separated by commas. Property changes are still printed.")
+                .short('e')
+                .long("events")
+                .value_name("event,...")
+                .value_delimiter(',')
+                .action(ArgAction::Append))
             .arg(Arg::new("property")
                 .num_args(0..)))
         .subcommand(Command::new("wait")
@@ -246,171 +438,212 @@ fn main() -> Result<(), Error> {
             .arg(Arg::new("property")
                 .num_args(1..)
                 .last(true)))
+        .subcommand(Command::new("log")
+            .about("Print mpv's log messages in real-time")
+            .arg(Arg::new("level")
+                .help("Only print messages of the given level or more severe")
+                .short('l')
+                .long("level")
+                .value_parser(["fatal", "error", "warn", "info", "status", "v", "debug", "trace", "stats"])
+                .default_value("info"))
+            .arg(Arg::new("prefix")
+                .help("Only print messages logged by the given module, e.g. 'cplayer' or a script name. May be given several times.")
+                .short('p')
+                .long("prefix")
+                .action(ArgAction::Append)))
+        .subcommand(Command::new("message")
+            .about("Exchange messages with mpv scripts and other clients")
+            .subcommand_required(true)
+            .subcommand(Command::new("send")
+                .about("Send a message to the script or client with the given name")
+                .arg(Arg::new("target")
+                    .required(true))
+                .arg(Arg::new("args")
+                    .num_args(0..)
+                    .allow_hyphen_values(true)))
+            .subcommand(Command::new("listen")
+                .about("Print the messages sent to mpvc, with their arguments separated by tabs. \
+                    Scripts can send them with 'script-message', or with 'script-message-to' and the client name printed on startup.")
+                .arg(Arg::new("json")
+                    .help("Print the arguments as a JSON array")
+                    .short('j')
+                    .long("json")
+                    .action(ArgAction::SetTrue))
+                .arg(Arg::new("name")
+                    .help("Only print messages whose first argument is one of the given names")
+                    .num_args(0..))))
         .subcommand(Command::new("completion")
             .about("Generate a shell completion script")
             .hide(true)
             .arg(Arg::new("shell")
                 .value_parser(EnumValueParser::<Shell>::new())
-                .required(true)));
-    let matches = cli.get_matches_mut();
+                .required(true)))
+}
 
+fn try_main(cli: &mut Command, matches: &ArgMatches) -> Result<(), Failure> {
     if let Some(("completion", completion_matches)) = matches.subcommand() {
         let shell = *completion_matches.get_one::<Shell>("shell").unwrap();
-        clap_complete::generate(shell, &mut cli, env!("CARGO_CRATE_NAME"), &mut io::stdout());
+        clap_complete::generate(shell, cli, env!("CARGO_CRATE_NAME"), &mut io::stdout());
         return Ok(());
     }
 
+    if let Some(state) = matches.get_one::<Map<String, Value>>("dry-run") {
+        let mut dry_run = DryRun::new(io::stdout());
+        dry_run.stub("pause", false)
+            .stub("mute", false)
+            .stub("volume", 100.0)
+            .stub("loop-file", false)
+            .stub("loop-playlist", false)
+            .stub("path", "file")
+            .stub("filename", "file")
+            .stub("media-title", "file")
+            .stub("playlist", json!([{ "filename": "file", "current": true, "playing": true, "id": 1 }]))
+            .stub("playlist-count", 1)
+            .stub("playlist-pos", 0)
+            .stub("playlist-pos-1", 1);
+        for (property, value) in state {
+            dry_run.stub(property, value.clone());
+        }
+        let mut mpv = Mpv::new(dry_run);
+        // Probing for the version would print its commands as well
+        mpv.set_capabilities(Capabilities::latest());
+        return run(&mut Player::new(mpv), matches);
+    }
+
     let socket = matches.get_one::<String>("socket").unwrap();
-    let mut mpv = match Mpv::connect(socket) {
+    let timeout = matches.get_one::<Duration>("timeout").copied();
+    let connect = |socket| match timeout {
+        Some(timeout) => Mpv::connect_timeout(socket, timeout),
+        None => Mpv::connect(socket),
+    };
+    let mpv = match connect(socket) {
         Ok(instance) => instance,
         Err(e) => {
             if let Some(("add", _)) = matches.subcommand() {
-                #[expect(clippy::zombie_processes)]
-                Cmd::new("mpv")
-                    .args([
-                        "--no-terminal",
-                        "--idle=once",
-                        "--vid=no",
-                        ("--input-ipc-server=".to_owned() + socket).as_str(),
-                    ])
-                    .spawn()
-                    .expect("mpv failed to start");
-                thread::sleep(Duration::from_millis(500));
-                Mpv::connect(socket)?
+                // mpv may take longer to start than to answer a command, so --timeout only
+                // applies to the commands sent once it is up
+                let mut mpv = MpvProcess::new(socket)
+                    .args(["--no-terminal", "--idle=once", "--vid=no"])
+                    .detach(true)
+                    .spawn()?;
+                mpv.set_timeout(timeout)?;
+                mpv
             } else {
-                return Err(e);
+                return Err(e.into());
             }
         }
     };
 
+    let mut player = Player::new(mpv);
+    run(&mut player, matches).map_err(|e| explain_unsupported(&mut player, e))
+}
+
+fn run<S: Transport>(mpv: &mut Player<S>, matches: &ArgMatches) -> Result<(), Failure> {
+    if let Some(path) = matches.get_one::<PathBuf>("record") {
+        mpv.record(File::create(path).map_err(|e| Failure::Record(path.clone(), e))?);
+    }
+
+    // Commands which don't listen for events would only accumulate them, unread
+    let listens = match matches.subcommand() {
+        Some(("observe" | "wait" | "log", _)) => true,
+        Some(("message", message_matches)) => message_matches.subcommand_name() == Some("listen"),
+        _ => false,
+    };
+    // A dry run never receives events, so these commands stop once their setup is printed
+    let dry_run = matches.contains_id("dry-run");
+    if !listens && !dry_run {
+        mpv.disable_event("all")?;
+    }
+
+    let switch = |matches: &ArgMatches| matches.get_one::<String>("arg").map(|arg| arg == "on");
+
     match matches.subcommand() {
-        Some(("play", _)) => mpv.set_property("pause", false)?,
-        Some(("pause", _)) => mpv.set_property("pause", true)?,
-        Some(("toggle", _)) => mpv.command_arg("cycle", ["pause"])?,
-        Some(("next", _)) => mpv.command("playlist-next")?,
-        Some(("prev", _)) => mpv.command("playlist-prev")?,
+        Some(("play", _)) => mpv.play()?,
+        Some(("pause", _)) => mpv.pause()?,
+        Some(("toggle", _)) => mpv.toggle_pause()?,
+        Some(("next", _)) => mpv.playlist_next()?,
+        Some(("prev", _)) => mpv.playlist_prev()?,
         Some(("seek", seek_matches)) => {
-            let target = seek_matches.get_one::<String>("target").unwrap().as_str();
-            let mode = seek_matches.get_one::<String>("mode").unwrap().as_str();
-            mpv.command_arg("seek", [target, mode])?;
+            let target = *seek_matches.get_one::<f64>("target").unwrap();
+            let seek = match seek_matches.get_one::<String>("mode").unwrap().as_str() {
+                "relative" => Seek::Relative(target),
+                "relative-percent" => Seek::RelativePercent(target),
+                "absolute" => Seek::Absolute(target),
+                "absolute-percent" => Seek::AbsolutePercent(target),
+                _ => unreachable!(),
+            };
+            mpv.seek(seek)?;
         }
-        Some(("restart", _)) => mpv.command_arg("seek", ["0", "absolute"])?,
-        Some(("kill", _)) => mpv.command("quit")?,
+        Some(("restart", _)) => mpv.restart()?,
+        Some(("kill", _)) => mpv.quit()?,
 
         Some(("add", add_matches)) => {
-            let command = match add_matches.get_one::<String>("type").unwrap().as_str() {
-                "file" => "loadfile",
-                "playlist" => "loadlist",
+            let mode = match add_matches.get_one::<String>("mode").unwrap().as_str() {
+                "replace" => LoadMode::Replace,
+                "append" => LoadMode::Append,
+                "append-play" => LoadMode::AppendPlay,
+                "insert-next" => LoadMode::InsertNext,
+                "insert-next-play" => LoadMode::InsertNextPlay,
+                "append-next" => {
+                    if !matches.get_flag("quiet") {
+                        eprintln!("{}: --mode append-next is deprecated, use insert-next instead", env!("CARGO_CRATE_NAME"));
+                    }
+                    LoadMode::InsertNext
+                }
                 _ => unreachable!(),
             };
-            let mode = add_matches.get_one::<String>("mode").unwrap().as_str();
-            for file in add_matches.get_many::<String>("file").unwrap() {
-                mpv.command_arg(command, [file.as_str(), mode])?;
+            let files = add_matches.get_many::<String>("file").unwrap();
+            match add_matches.get_one::<String>("type").unwrap().as_str() {
+                "file" => mpv.add_files(files, mode)?,
+                "playlist" => mpv.add_playlists(files, mode)?,
+                _ => unreachable!(),
             }
         }
 
         Some(("playlist", _)) => {
-            let property = mpv.get_property("playlist")?;
-            let playlist = property.as_array().ok_or(Error::UnexpectedValue)?;
-            for (i, e) in playlist.iter().enumerate() {
-                let entry = e.as_object().ok_or(Error::UnexpectedValue)?;
-                let title = if let Some(title) = entry.get("title") {
-                    title
-                } else if let Some(filename) = entry.get("filename") {
-                    filename
-                } else {
-                    return Err(Error::MissingValue);
-                }.as_str().ok_or(Error::UnexpectedValue)?;
-                let mut output = format!("{}\t{title}", i + 1);
-                if entry.contains_key("current") {
+            for (i, entry) in mpv.playlist()?.iter().enumerate() {
+                let mut output = format!("{}\t{}", i + 1, entry.display_name());
+                if entry.current {
                     output = output.reversed().to_string();
                 }
                 println!("{output}");
             }
         }
 
-        Some(("stop", _)) => mpv.command("stop")?,
-        Some(("clear", _)) => mpv.command("playlist-clear")?,
-
-        Some(("remove", remove_matches)) => {
-            let value: Value = remove_matches.get_one::<u64>("id").map_or_else(|| "current".into(), |id| (*id).into());
-            mpv.command_arg("playlist-remove", [value])?;
-        }
+        Some(("stop", _)) => mpv.stop()?,
+        Some(("clear", _)) => mpv.clear_playlist()?,
+        Some(("remove", remove_matches)) => mpv.remove(remove_matches.get_one::<u64>("id").copied())?,
 
         Some(("move", move_matches)) => {
             let from = *move_matches.get_one::<u64>("from").unwrap();
             let to = *move_matches.get_one::<u64>("to").unwrap();
-            mpv.command_arg("playlist-move", [from, to])?;
-        }
-
-        Some(("play-next", play_next_matches)) => {
-            let id = *play_next_matches.get_one::<u64>("id").unwrap();
-            let pos = mpv.get_property("playlist-pos")?.as_u64().ok_or(Error::UnexpectedValue)?;
-            mpv.command_arg("playlist-move", [id, pos + 1])?;
-        }
-
-        Some(("position", position_matches)) => {
-            let id = *position_matches.get_one::<u64>("id").unwrap();
-            mpv.command_arg("playlist-play-index", [id])?;
+            mpv.move_entry(from, to)?;
         }
 
-        Some(("shuffle", _)) => mpv.command("playlist-shuffle")?,
-
-        Some(("reverse", _)) => {
-            let count = mpv.get_property("playlist-count")?.as_u64().ok_or(Error::UnexpectedValue)? - 1;
-            for i in 0..count {
-                mpv.command_arg("playlist-move", [count, i])?;
-            }
-        }
-
-        Some(("loop-file", loop_file_matches)) => {
-            match loop_file_matches.get_one::<String>("arg") {
-                Some(arg) => match arg.as_str() {
-                    "on" => mpv.set_property("loop-file", "inf")?,
-                    "off" => mpv.set_property("loop-file", false)?,
-                    _ => unreachable!(),
-                },
-                None => mpv.command_arg("cycle-values", ["loop-file".into(), Value::Bool(false), "inf".into()])?,
-            }
-        }
-
-        Some(("loop-playlist", loop_playlist_matches)) => {
-            match loop_playlist_matches.get_one::<String>("arg") {
-                Some(arg) => match arg.as_str() {
-                    "on" => mpv.set_property("loop-playlist", "inf")?,
-                    "off" => mpv.set_property("loop-playlist", false)?,
-                    _ => unreachable!(),
-                },
-                None => mpv.command_arg("cycle-values", ["loop-playlist".into(), Value::Bool(false), "inf".into()])?,
-            }
-        }
+        Some(("play-next", play_next_matches)) => mpv.play_next(*play_next_matches.get_one::<u64>("id").unwrap())?,
+        Some(("position", position_matches)) => mpv.play_index(*position_matches.get_one::<u64>("id").unwrap())?,
+        Some(("shuffle", _)) => mpv.shuffle()?,
+        Some(("reverse", _)) => mpv.reverse_playlist()?,
+        Some(("loop-file", loop_file_matches)) => mpv.set_loop_file(switch(loop_file_matches))?,
+        Some(("loop-playlist", loop_playlist_matches)) => mpv.set_loop_playlist(switch(loop_playlist_matches))?,
 
         Some(("volume", volume_matches)) => {
             let num = *volume_matches.get_one::<f64>("num").unwrap();
             match volume_matches.get_one::<String>("mode").unwrap().as_str() {
-                "absolute" => mpv.set_property("volume", num)?,
-                "relative" => mpv.add_property("volume", num)?,
+                "absolute" => mpv.set_volume(num)?,
+                "relative" => mpv.adjust_volume(num)?,
                 _ => unreachable!(),
             }
         }
 
-        Some(("mute", mute_matches)) => {
-            match mute_matches.get_one::<String>("arg") {
-                Some(arg) => match arg.as_str() {
-                    "on" => mpv.set_property("mute", true)?,
-                    "off" => mpv.set_property("mute", false)?,
-                    _ => unreachable!(),
-                },
-                None => mpv.command_arg("cycle", ["mute"])?,
-            }
-        }
+        Some(("mute", mute_matches)) => mpv.set_mute(switch(mute_matches))?,
 
         Some(("set", set_matches)) => {
             let property = set_matches.get_one::<String>("property").unwrap();
             let value = set_matches.get_one::<String>("value").unwrap();
             let json = *set_matches.get_one::<bool>("json").unwrap();
             let value = if json {
-                value.parse::<Value>().map_err(Error::JsonError)?
+                parse_json(value)?
             } else {
                 value.as_str().into()
             };
@@ -424,7 +657,7 @@ fn main() -> Result<(), Error> {
             if json {
                 println!("{value}");
             } else {
-                println!("{}", value_to_string(&value)?);
+                println!("{}", value_to_string(&value).map_err(|_| Failure::Missing(format!("property '{property}'")))?);
             }
         }
 
@@ -432,23 +665,28 @@ fn main() -> Result<(), Error> {
             let command = run_matches.get_one::<String>("command").unwrap();
             let args = run_matches.get_many::<String>("args").unwrap_or_default()
                 .map(|v| v.as_str().into());
-            let json = if let Some(json) = run_matches.get_many::<String>("json") {
-                json.map(|v| v.parse::<Value>())
-                    .collect::<Result<Vec<_>, _>>().map_err(Error::JsonError)?
+            let json_args = if let Some(json_args) = run_matches.get_many::<String>("json-args") {
+                json_args.map(|v| parse_json(v)).collect::<Result<Vec<_>, _>>()?
             } else {
                 Vec::new()
             };
-            mpv.command_arg(command, args.chain(json))?;
+            let json = *run_matches.get_one::<bool>("json").unwrap();
+            let value = mpv.command_value(command, args.chain(json_args))?;
+            match value {
+                Value::Null => (),
+                _ if json => println!("{value}"),
+                _ => println!("{}", value_to_string(&value)?),
+            }
         }
 
         Some(("metadata", metadata_matches)) => {
             let attribute = metadata_matches.get_one::<String>("attribute").unwrap().as_str();
             let metadata = mpv.get_property(("metadata/by-key/".to_owned() + attribute).as_str())?;
-            println!("{}", value_to_string(&metadata)?);
+            println!("{}", value_to_string(&metadata).map_err(|_| Failure::Missing(format!("metadata key '{attribute}'")))?);
         }
 
         Some(("format", format_matches)) => {
-            fn eval_format(mpv: &mut Mpv, metadata: &Map<String, Value>, spec: &str) -> Option<String> {
+            fn eval_format<S: Transport>(mpv: &mut Mpv<S>, metadata: &Metadata, spec: &str) -> Option<String> {
                 fn format_duration(d: u64) -> String {
                     match (d % 60, (d / 60) % 60, d / 3600) {
                         (s, m, 0) => format!("{m:02}:{s:02}"),
@@ -462,7 +700,7 @@ fn main() -> Result<(), Error> {
                     "[" | "]" => Some(spec.to_owned()),
                     "title" => {
                         if let Some(title) = metadata.get("title") {
-                            Some(title.as_str()?.to_owned())
+                            Some(title.to_owned())
                         } else {
                             Some(mpv.get_property("media-title").ok()?.as_str()?.to_owned())
                         }
@@ -481,7 +719,7 @@ fn main() -> Result<(), Error> {
                             Some(pair[j + 1..].to_owned())
                         }
                     }
-                    _ if let Some(metadata) = metadata.get(spec) => value_to_string(metadata).ok(),
+                    _ if let Some(metadata) = metadata.get(spec) => Some(metadata.to_owned()),
                     _ => value_to_string(&mpv.get_property(spec).ok()?).ok(),
                 }
             }
@@ -496,10 +734,9 @@ fn main() -> Result<(), Error> {
             let mut input = format_matches.get_one::<String>("format-string").unwrap().as_str();
             let mut output = String::with_capacity(input.len());
             let metadata = if let Ok(metadata) = mpv.get_property("metadata") {
-                metadata.as_object().ok_or(Error::UnexpectedValue)?
-                    .iter().map(|(k, v)| (k.to_lowercase(), v.clone())).collect()
+                serde_json::from_value(metadata).map_err(Error::JsonError)?
             } else {
-                Map::<String, Value>::new()
+                Metadata::default()
             };
 
             let mut state = Raw;
@@ -542,7 +779,7 @@ fn main() -> Result<(), Error> {
                             // Unterminated format specifier
                             break;
                         };
-                        if let Some(s) = eval_format(&mut mpv, &metadata, &input[..i]) {
+                        if let Some(s) = eval_format(mpv, &metadata, &input[..i]) {
                             if let Some(last) = stack.last_mut() {
                                 *last += s.as_str();
                             } else {
@@ -588,8 +825,15 @@ fn main() -> Result<(), Error> {
 
         Some(("observe", observe_matches)) => {
             let properties = observe_matches.get_many::<String>("property").map_or_else(Vec::new, Iterator::collect);
-            for (i, property) in properties.into_iter().enumerate() {
-                mpv.observe_property(i as isize + 1, property)?;
+            if let Some(events) = observe_matches.get_many::<String>("events") {
+                mpv.disable_event("all")?;
+                for event in events.map(String::as_str).chain(["property-change"]) {
+                    mpv.enable_event(event)?;
+                }
+            }
+            let _observers = properties.into_iter().map(|property| mpv.observe(property)).collect::<Result<Vec<_>, _>>()?;
+            if dry_run {
+                return Ok(());
             }
             while let Ok(response) = mpv.listen_raw() {
                 println!("{response}");
@@ -599,28 +843,94 @@ fn main() -> Result<(), Error> {
         Some(("wait", wait_matches)) => {
             let events = wait_matches.get_many::<String>("event").map_or_else(Vec::new, Iterator::collect);
             let properties = wait_matches.get_many::<String>("property").map_or_else(Vec::new, Iterator::collect);
-            for (i, property) in properties.iter().enumerate() {
-                mpv.observe_property(i as isize + 1, property)?;
+            let observers = properties.into_iter().map(|property| mpv.observe(property)).collect::<Result<Vec<_>, _>>()?;
+            if dry_run {
+                return Ok(());
             }
             // Needed since the observe_property command itself emits a property-change event
             let mut seen = Vec::new();
             loop {
-                let event = mpv.listen()?;
-                if let Some(Value::String(e)) = event.get("event") {
-                    if e == "property-change"
-                            && let Some(Value::String(property)) = event.get("name")
-                            && let Some(i) = properties.iter().position(|v| v == &property) {
-                        if seen.contains(&property) {
-                            break;
-                        }
-                        seen.push(properties[i]);
-                    } else if events.contains(&e) {
+                let event = mpv.listen_event()?;
+                if let Some(observer) = observers.iter().find(|observer| observer.matches(&event)) {
+                    if seen.contains(&observer.id()) {
                         break;
                     }
+                    seen.push(observer.id());
+                } else if events.iter().any(|e| *e == event.name()) {
+                    break;
+                }
+            }
+        }
+
+        Some(("log", log_matches)) => {
+            let level = match log_matches.get_one::<String>("level").unwrap().as_str() {
+                "fatal" => LogLevel::Fatal,
+                "error" => LogLevel::Error,
+                "warn" => LogLevel::Warn,
+                "info" => LogLevel::Info,
+                "status" => LogLevel::Status,
+                "v" => LogLevel::Verbose,
+                "debug" => LogLevel::Debug,
+                "trace" => LogLevel::Trace,
+                "stats" => LogLevel::Stats,
+                _ => unreachable!(),
+            };
+            let prefixes = log_matches.get_many::<String>("prefix").map_or_else(Vec::new, Iterator::collect);
+            mpv.request_log_messages(Some(level))?;
+            if dry_run {
+                return Ok(());
+            }
+            loop {
+                match mpv.listen_event()? {
+                    Event::LogMessage(message) if prefixes.is_empty() || prefixes.contains(&&message.prefix) => {
+                        let line = format!("[{}] {}", message.prefix, message.text.trim_end_matches('\n'));
+                        let line = match message.level {
+                            LogLevel::Fatal => line.red().bold(),
+                            LogLevel::Error => line.red(),
+                            LogLevel::Warn => line.yellow(),
+                            LogLevel::Info | LogLevel::Status => line.normal(),
+                            LogLevel::Verbose | LogLevel::Debug | LogLevel::Trace | LogLevel::Stats => line.dimmed(),
+                        };
+                        println!("{line}");
+                    }
+                    Event::Shutdown => break,
+                    _ => (),
                 }
             }
         }
 
+        Some(("message", message_matches)) => match message_matches.subcommand() {
+            Some(("send", send_matches)) => {
+                let target = send_matches.get_one::<String>("target").unwrap();
+                let args = send_matches.get_many::<String>("args").unwrap_or_default();
+                mpv.script_message_to(target, args.cloned())?;
+            }
+            Some(("listen", listen_matches)) => {
+                let json = listen_matches.get_flag("json");
+                let names = listen_matches.get_many::<String>("name").map_or_else(Vec::new, Iterator::collect);
+                if !matches.get_flag("quiet") && let Ok(name) = mpv.client_name() {
+                    eprintln!("{}: listening as {name}", env!("CARGO_CRATE_NAME"));
+                }
+                if dry_run {
+                    return Ok(());
+                }
+                loop {
+                    match mpv.listen_event()? {
+                        Event::ClientMessage { args } if names.is_empty() || args.first().is_some_and(|name| names.contains(&name)) => {
+                            if json {
+                                println!("{}", Value::from(args));
+                            } else {
+                                println!("{}", args.join("\t"));
+                            }
+                        }
+                        Event::Shutdown => break,
+                        _ => (),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        },
+
         _ => unreachable!(),
     }
 
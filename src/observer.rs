@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+use crate::Event;
+
+/// A property observed with [`Mpv::observe`](crate::Mpv::observe). The property is unobserved
+/// once this is dropped, with the next command sent on the connection.
+#[derive(Debug)]
+pub struct Observer {
+    id: isize,
+    property: String,
+    dropped: Arc<Mutex<Vec<isize>>>,
+}
+
+impl Drop for Observer {
+    fn drop(&mut self) {
+        self.dropped.lock().unwrap_or_else(|e| e.into_inner()).push(self.id);
+    }
+}
+
+impl Observer {
+    pub(crate) fn new(id: isize, property: &str, dropped: &Arc<Mutex<Vec<isize>>>) -> Self {
+        Self {
+            id,
+            property: property.to_owned(),
+            dropped: Arc::clone(dropped),
+        }
+    }
+
+    /// The id the property is observed under.
+    pub fn id(&self) -> isize {
+        self.id
+    }
+
+    /// The name of the observed property.
+    pub fn property(&self) -> &str {
+        &self.property
+    }
+
+    /// Whether the event is a change of the observed property.
+    pub fn matches(&self, event: &Event) -> bool {
+        matches!(*event, Event::PropertyChange { id, .. } if id == self.id as i64)
+    }
+}
@@ -0,0 +1,294 @@
+use core::ops::{Deref, DerefMut};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{Error, Mpv, MpvCommand, PlaylistEntry, Transport, number};
+
+/// A seek target, see [`Player::seek`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seek {
+    /// Seek by the given amount of seconds, backwards if negative.
+    Relative(f64),
+    /// Seek to the given position in seconds, counted from the end of the file if negative.
+    Absolute(f64),
+    /// Seek by the given percentage of the file's duration.
+    RelativePercent(f64),
+    /// Seek to the given percentage of the file's duration.
+    AbsolutePercent(f64),
+}
+
+/// Where to put loaded files in the playlist, see [`Player::add_files`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Stop playback of the current file and play the new file immediately.
+    Replace,
+    /// Append the file to the playlist.
+    Append,
+    /// Append the file, and start playback if nothing is playing.
+    #[default]
+    AppendPlay,
+    /// Insert the file directly after the current entry.
+    InsertNext,
+    /// Insert the file directly after the current entry, and start playback if nothing is playing.
+    InsertNextPlay,
+    /// Insert the file at the given index of the playlist (starting at 0). The file is appended
+    /// if the index is out of range.
+    InsertAt(i64),
+    /// Insert the file at the given index, and start playback if nothing is playing.
+    InsertAtPlay(i64),
+}
+
+impl LoadMode {
+    /// The name of the mode as used by mpv, e.g. `"append-play"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Replace => "replace",
+            Self::Append => "append",
+            Self::AppendPlay => "append-play",
+            Self::InsertNext => "insert-next",
+            Self::InsertNextPlay => "insert-next-play",
+            Self::InsertAt(_) => "insert-at",
+            Self::InsertAtPlay(_) => "insert-at-play",
+        }
+    }
+
+    /// The index passed along with the mode, for `insert-at` and `insert-at-play`.
+    pub fn index(self) -> Option<i64> {
+        match self {
+            Self::InsertAt(index) | Self::InsertAtPlay(index) => Some(index),
+            _ => None,
+        }
+    }
+}
+
+/// A high-level interface for controlling playback, with the same behavior as the mpvc CLI.
+///
+/// All methods of [`Mpv`] remain available through `Deref`.
+///
+/// ```no_run
+/// # use mpvc::{Error, Mpv, Player, Seek};
+/// # fn main() -> Result<(), Error> {
+/// let mut player = Player::new(Mpv::connect("/tmp/mpvsocket")?);
+/// player.toggle_pause()?;
+/// player.seek(Seek::Relative(-10.0))?;
+/// player.set_loop_file(Some(true))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Player<S: Transport = UnixStream> {
+    mpv: Mpv<S>,
+}
+
+impl<S: Transport> Deref for Player<S> {
+    type Target = Mpv<S>;
+
+    fn deref(&self) -> &Mpv<S> {
+        &self.mpv
+    }
+}
+
+impl<S: Transport> DerefMut for Player<S> {
+    fn deref_mut(&mut self) -> &mut Mpv<S> {
+        &mut self.mpv
+    }
+}
+
+impl<S: Transport> Player<S> {
+    /// Control mpv through the given connection.
+    pub fn new(mpv: Mpv<S>) -> Self {
+        Self { mpv }
+    }
+
+    /// Return the underlying connection.
+    pub fn into_inner(self) -> Mpv<S> {
+        self.mpv
+    }
+
+    /// Resume playback.
+    pub fn play(&mut self) -> Result<(), Error> {
+        self.mpv.set_property("pause", false)
+    }
+
+    /// Pause playback.
+    pub fn pause(&mut self) -> Result<(), Error> {
+        self.mpv.set_property("pause", true)
+    }
+
+    /// Pause playback if it is running, and resume it otherwise.
+    pub fn toggle_pause(&mut self) -> Result<(), Error> {
+        self.cycle("pause")
+    }
+
+    /// Play the next entry in the playlist.
+    pub fn playlist_next(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistNext { force: false })
+    }
+
+    /// Play the previous entry in the playlist.
+    pub fn playlist_prev(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistPrev { force: false })
+    }
+
+    /// Change the playback position.
+    pub fn seek(&mut self, seek: Seek) -> Result<(), Error> {
+        self.run(MpvCommand::Seek { target: seek, exact: None })
+    }
+
+    /// Restart playback of the current file.
+    pub fn restart(&mut self) -> Result<(), Error> {
+        self.seek(Seek::Absolute(0.0))
+    }
+
+    /// Quit mpv.
+    pub fn quit(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::Quit(None))
+    }
+
+    /// Load the given files into the playlist. The insert modes are emulated on mpv versions
+    /// older than 0.38, which don't have them.
+    pub fn add_files<I: IntoIterator>(&mut self, files: I, mode: LoadMode) -> Result<(), Error>
+    where I::Item: AsRef<Path> {
+        self.load("loadfile", files, mode, |url, flags| MpvCommand::LoadFile { url, flags, options: Vec::new() })
+    }
+
+    /// Load the entries of the given playlist files into the playlist. The insert modes are
+    /// emulated as with [`add_files`](Self::add_files).
+    pub fn add_playlists<I: IntoIterator>(&mut self, playlists: I, mode: LoadMode) -> Result<(), Error>
+    where I::Item: AsRef<Path> {
+        self.load("loadlist", playlists, mode, |url, flags| MpvCommand::LoadList { url, flags })
+    }
+
+    /// The entries of the playlist.
+    pub fn playlist(&mut self) -> Result<Vec<PlaylistEntry>, Error> {
+        self.mpv.get_property_as("playlist")
+    }
+
+    /// Stop playback and clear the playlist.
+    pub fn stop(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::Stop { keep_playlist: false })
+    }
+
+    /// Clear the playlist, except for the current entry.
+    pub fn clear_playlist(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistClear)
+    }
+
+    /// Remove the playlist entry at the given index (starting at 0), or the current entry if
+    /// `None`. Playback stops if the removed entry is playing.
+    pub fn remove(&mut self, index: Option<u64>) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistRemove(index))
+    }
+
+    /// Move the playlist entry at the index `from` to the index `to`.
+    pub fn move_entry(&mut self, from: u64, to: u64) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistMove { from, to })
+    }
+
+    /// Move the playlist entry at the given index right after the current entry.
+    pub fn play_next(&mut self, index: u64) -> Result<(), Error> {
+        let pos = self.mpv.get_property_as::<u64>("playlist-pos")?;
+        self.move_entry(index, pos + 1)
+    }
+
+    /// Play the playlist entry at the given index. Falls back to setting `playlist-pos` on mpv
+    /// versions older than 0.34.
+    pub fn play_index(&mut self, index: u64) -> Result<(), Error> {
+        if self.mpv.capabilities()?.supports_command("playlist-play-index") {
+            self.run(MpvCommand::PlaylistPlayIndex(index))
+        } else {
+            self.mpv.set_property("playlist-pos", index)
+        }
+    }
+
+    /// Shuffle the playlist.
+    pub fn shuffle(&mut self) -> Result<(), Error> {
+        self.run(MpvCommand::PlaylistShuffle)
+    }
+
+    /// Reverse the order of the playlist.
+    pub fn reverse_playlist(&mut self) -> Result<(), Error> {
+        let count = self.mpv.get_property_as::<u64>("playlist-count")?.saturating_sub(1);
+        self.batch((0..count).map(|i| MpvCommand::PlaylistMove { from: count, to: i }))
+    }
+
+    /// Set whether to repeat the current file, or toggle it if `None`.
+    pub fn set_loop_file(&mut self, enable: Option<bool>) -> Result<(), Error> {
+        self.set_loop("loop-file", enable)
+    }
+
+    /// Set whether to repeat the playlist, or toggle it if `None`.
+    pub fn set_loop_playlist(&mut self, enable: Option<bool>) -> Result<(), Error> {
+        self.set_loop("loop-playlist", enable)
+    }
+
+    fn set_loop(&mut self, property: &str, enable: Option<bool>) -> Result<(), Error> {
+        match enable {
+            Some(true) => self.mpv.set_property(property, "inf"),
+            Some(false) => self.mpv.set_property(property, false),
+            None => self.run(MpvCommand::CycleValues {
+                property: property.to_owned(),
+                values: vec![false.into(), "inf".into()],
+                reverse: false,
+            }),
+        }
+    }
+
+    /// Set the volume level.
+    pub fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+        self.mpv.set_property("volume", number(volume)?)
+    }
+
+    /// Change the volume level by the given amount, decreasing it if negative.
+    pub fn adjust_volume(&mut self, amount: f64) -> Result<(), Error> {
+        self.mpv.add_property("volume", amount)
+    }
+
+    /// Set whether audio is muted, or toggle it if `None`.
+    pub fn set_mute(&mut self, mute: Option<bool>) -> Result<(), Error> {
+        match mute {
+            Some(mute) => self.mpv.set_property("mute", mute),
+            None => self.cycle("mute"),
+        }
+    }
+
+    fn cycle(&mut self, property: &str) -> Result<(), Error> {
+        self.run(MpvCommand::Cycle { property: property.to_owned(), down: false })
+    }
+
+    fn run(&mut self, command: MpvCommand) -> Result<(), Error> {
+        self.mpv.execute(&command).map(|_| ())
+    }
+
+    fn load<I, F>(&mut self, name: &str, urls: I, mode: LoadMode, command: F) -> Result<(), Error>
+    where I: IntoIterator, I::Item: AsRef<Path>, F: Fn(String, LoadMode) -> MpvCommand {
+        let urls = urls.into_iter().map(|url| url.as_ref().to_string_lossy().into_owned()).collect::<Vec<_>>();
+        let fallback = match mode {
+            LoadMode::InsertNext | LoadMode::InsertAt(_) => LoadMode::Append,
+            LoadMode::InsertNextPlay | LoadMode::InsertAtPlay(_) => LoadMode::AppendPlay,
+            _ => mode,
+        };
+        // The index argument was added along with the insert modes
+        if fallback == mode || self.mpv.capabilities()?.supports_argument(name, "index") {
+            return self.batch(urls.into_iter().map(|url| command(url, mode)));
+        }
+
+        let count = self.mpv.get_property_as::<u64>("playlist-count")?;
+        let to = match mode.index() {
+            Some(index) => u64::try_from(index).ok().filter(|&index| index <= count).unwrap_or(count),
+            None => self.mpv.get_property_as::<i64>("playlist-pos")?.saturating_add(1).max(0).cast_unsigned(),
+        };
+        self.batch(urls.into_iter().map(|url| command(url, fallback)))?;
+        // The number of entries in a playlist file is only known once it's loaded
+        let added = self.mpv.get_property_as::<u64>("playlist-count")?.saturating_sub(count);
+        self.batch((0..added).map(|i| MpvCommand::PlaylistMove { from: count + i, to: to + i }))
+    }
+
+    fn batch<I: IntoIterator<Item = MpvCommand>>(&mut self, commands: I) -> Result<(), Error> {
+        let commands = commands.into_iter().map(Value::try_from).collect::<Result<Vec<_>, _>>()?;
+        self.mpv.command_batch(commands)?
+            .into_iter().try_for_each(|result| result.map(|_| ()))
+    }
+}
@@ -0,0 +1,160 @@
+use core::time::Duration;
+use std::ffi::{OsStr, OsString};
+use std::io::Error as IoError;
+use std::os::fd::AsRawFd as _;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt as _;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use crate::{Error, Mpv};
+
+#[derive(Clone, Debug)]
+enum Ipc {
+    Server(PathBuf),
+    SocketPair,
+}
+
+/// A builder for spawning an mpv process and connecting to it.
+///
+/// The IPC socket is set up by the builder, and [`MpvProcess::spawn`] only returns once it
+/// accepts connections. Alternatively, mpv can be handed one end of a socket pair with
+/// [`MpvProcess::with_socketpair`], which doesn't need a socket in the filesystem. The child
+/// process is reaped in the background once it exits, so it doesn't have to be waited on.
+///
+/// ```no_run
+/// # use mpvc::{Error, MpvProcess};
+/// # fn main() -> Result<(), Error> {
+/// let mut mpv = MpvProcess::new("/tmp/mpvsocket")
+///     .args(["--no-terminal", "--idle=once"])
+///     .detach(true)
+///     .spawn()?;
+/// mpv.command_arg("loadfile", ["music.flac"])?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MpvProcess {
+    program: OsString,
+    args: Vec<OsString>,
+    ipc: Ipc,
+    timeout: Duration,
+    detach: bool,
+}
+
+impl MpvProcess {
+    /// Create a builder for an mpv process listening on the socket at the given path.
+    pub fn new<P: Into<PathBuf>>(socket: P) -> Self {
+        Self::with_ipc(Ipc::Server(socket.into()))
+    }
+
+    /// Create a builder for an mpv process which is connected through a socket pair, using
+    /// `--input-ipc-client`. Nothing else can connect to such a process.
+    pub fn with_socketpair() -> Self {
+        Self::with_ipc(Ipc::SocketPair)
+    }
+
+    fn with_ipc(ipc: Ipc) -> Self {
+        Self {
+            program: "mpv".into(),
+            args: Vec::new(),
+            ipc,
+            timeout: Duration::from_secs(5),
+            detach: false,
+        }
+    }
+
+    /// Set the mpv executable to run. Defaults to `mpv`, looked up in `PATH`.
+    pub fn program<S: AsRef<OsStr>>(&mut self, program: S) -> &mut Self {
+        program.as_ref().clone_into(&mut self.program);
+        self
+    }
+
+    /// Pass an additional argument to mpv.
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Pass additional arguments to mpv.
+    pub fn args<I: IntoIterator>(&mut self, args: I) -> &mut Self
+    where I::Item: AsRef<OsStr> {
+        self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Set how long to wait for the IPC socket to accept connections. Defaults to 5 seconds. Has no
+    /// effect when connecting through a socket pair.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run mpv in a new session with `setsid`, so that it outlives the terminal it was started
+    /// from. Defaults to `false`.
+    pub fn detach(&mut self, detach: bool) -> &mut Self {
+        self.detach = detach;
+        self
+    }
+
+    /// Spawn mpv and connect to it once its IPC socket is ready. Fails with `Error::Timeout` if the
+    /// socket didn't come up in time, in which case mpv is killed.
+    pub fn spawn(&self) -> Result<Mpv, Error> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).stdin(Stdio::null());
+        if self.detach {
+            // SAFETY: setsid is async-signal-safe
+            unsafe {
+                command.pre_exec(|| if libc::setsid() == -1 { Err(IoError::last_os_error()) } else { Ok(()) });
+            }
+        }
+
+        let (mut child, result) = match self.ipc {
+            Ipc::Server(ref socket) => {
+                command.arg({
+                    let mut arg = OsString::from("--input-ipc-server=");
+                    arg.push(socket);
+                    arg
+                });
+                let mut child = command.spawn().map_err(Error::SpawnError)?;
+                let result = self.wait_for_socket(&mut child, socket);
+                (child, result)
+            }
+            Ipc::SocketPair => {
+                let (ours, theirs) = UnixStream::pair().map_err(Error::SpawnError)?;
+                let fd = theirs.as_raw_fd();
+                command.arg(format!("--input-ipc-client=fd://{fd}"));
+                // SAFETY: fcntl is async-signal-safe
+                unsafe {
+                    // Let the child inherit its end of the pair
+                    command.pre_exec(move || if libc::fcntl(fd, libc::F_SETFD, 0) == -1 { Err(IoError::last_os_error()) } else { Ok(()) });
+                }
+                let child = command.spawn().map_err(Error::SpawnError)?;
+                (child, Ok(Mpv::from_stream(ours)))
+            }
+        };
+
+        // Reap the child once it exits
+        thread::spawn(move || child.wait());
+        result
+    }
+
+    fn wait_for_socket(&self, child: &mut Child, socket: &Path) -> Result<Mpv, Error> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match Mpv::connect(socket) {
+                Ok(mpv) => return Ok(mpv),
+                Err(_) if let Ok(Some(status)) = child.try_wait() => {
+                    return Err(Error::SpawnError(IoError::other(format!("mpv exited prematurely ({status})"))));
+                }
+                Err(_) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    return Err(Error::Timeout);
+                }
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// An entry of the `playlist` property.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlaylistEntry {
+    pub filename: String,
+    #[serde(default)]
+    pub current: bool,
+    #[serde(default)]
+    pub playing: bool,
+    pub title: Option<String>,
+    pub id: Option<u64>,
+    pub playlist_path: Option<String>,
+}
+
+impl PlaylistEntry {
+    /// The title of the entry if known, otherwise its filename.
+    pub fn display_name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.filename)
+    }
+}
+
+/// An entry of the `track-list` property.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Track {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub src_id: Option<u64>,
+    pub title: Option<String>,
+    pub lang: Option<String>,
+    pub codec: Option<String>,
+    pub external_filename: Option<String>,
+    #[serde(default)]
+    pub image: bool,
+    #[serde(default)]
+    pub albumart: bool,
+    #[serde(default)]
+    pub default: bool,
+    #[serde(default)]
+    pub forced: bool,
+    #[serde(default)]
+    pub external: bool,
+    #[serde(default)]
+    pub selected: bool,
+}
+
+/// An entry of the `chapter-list` property.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Chapter {
+    pub title: Option<String>,
+    pub time: f64,
+}
+
+/// An entry of the `audio-device-list` property.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct AudioDevice {
+    pub name: String,
+    pub description: String,
+}
+
+/// The `metadata` property of the currently playing file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Metadata(pub BTreeMap<String, String>);
+
+impl Metadata {
+    /// Look up a metadata attribute by its exact key. Note that the case of the keys depends on
+    /// the container format.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+}
@@ -0,0 +1,146 @@
+use core::sync::atomic::{AtomicI64, Ordering};
+use std::collections::HashMap;
+use std::io::ErrorKind as IoErrorKind;
+use std::sync::{Mutex, MutexGuard, mpsc};
+
+use log::debug;
+use serde_json::{Map, Value};
+
+use crate::{Error, Event, encode_command, reply_value};
+
+/// Where the reply to a single command is delivered.
+pub(crate) trait ReplySender {
+    fn send_reply(self, reply: Map<String, Value>);
+}
+
+/// Where events are delivered.
+pub(crate) trait EventSender {
+    /// Deliver an event, returning whether the receiver still exists.
+    fn send_event(&self, event: &Event) -> bool;
+}
+
+impl ReplySender for mpsc::Sender<Map<String, Value>> {
+    fn send_reply(self, reply: Map<String, Value>) {
+        let _ = self.send(reply);
+    }
+}
+
+impl EventSender for mpsc::Sender<Event> {
+    fn send_event(&self, event: &Event) -> bool {
+        self.send(event.clone()).is_ok()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ReplySender for tokio::sync::oneshot::Sender<Map<String, Value>> {
+    fn send_reply(self, reply: Map<String, Value>) {
+        let _ = self.send(reply);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl EventSender for tokio::sync::mpsc::UnboundedSender<Event> {
+    fn send_event(&self, event: &Event) -> bool {
+        self.send(event.clone()).is_ok()
+    }
+}
+
+struct State<R, E> {
+    pending: HashMap<i64, R>,
+    subscribers: Vec<E>,
+    closed: bool,
+}
+
+/// The bookkeeping of the clients whose connection is read by a background reader, i.e.
+/// [`SharedMpv`](crate::SharedMpv) and `AsyncMpv`. Request ids are allocated here, every message
+/// read from the connection is routed either to the caller waiting for the reply with its
+/// `request_id` or to all event subscribers, and everyone still waiting is woken up once the
+/// connection is closed.
+pub(crate) struct Router<R, E> {
+    state: Mutex<State<R, E>>,
+    counter: AtomicI64,
+}
+
+/// The error for commands which can't be answered, because the connection is closed.
+fn disconnected() -> Error {
+    Error::ReadError(IoErrorKind::UnexpectedEof.into())
+}
+
+/// Extract the result of a command from its reply, which is `None` if the connection was closed
+/// before the reply arrived.
+pub(crate) fn reply_result(reply: Option<Map<String, Value>>, command: &Value) -> Result<Value, Error> {
+    reply.map_or_else(|| Err(disconnected()), |map| reply_value(map, command))
+}
+
+impl<R: ReplySender, E: EventSender> Router<R, E> {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(State { pending: HashMap::new(), subscribers: Vec::new(), closed: false }),
+            counter: AtomicI64::new(0),
+        }
+    }
+
+    /// Lock the state. A poisoned lock is recovered, since the state is only changed by single
+    /// insertions and removals, which leave it consistent even if a thread panicked meanwhile.
+    fn lock(&self) -> MutexGuard<'_, State<R, E>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a command whose reply is delivered to the given sender, and return its request id
+    /// along with the line to send. Fails if the connection is already closed.
+    pub(crate) fn register(&self, command: Value, tx: R) -> Result<(i64, String), Error> {
+        let request_id = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.lock();
+        if state.closed {
+            return Err(disconnected());
+        }
+        state.pending.insert(request_id, tx);
+        drop(state);
+
+        let request = encode_command(command, request_id);
+        debug!("Command: {request}");
+        Ok((request_id, request + "\n"))
+    }
+
+    /// Forget a command which couldn't be sent.
+    pub(crate) fn cancel(&self, request_id: i64) {
+        self.lock().pending.remove(&request_id);
+    }
+
+    /// Deliver all events received from now on to the given sender. It is dropped right away if
+    /// the connection is already closed.
+    pub(crate) fn subscribe(&self, tx: E) {
+        let mut state = self.lock();
+        if !state.closed {
+            state.subscribers.push(tx);
+        }
+    }
+
+    /// Route a line read from the connection.
+    pub(crate) fn dispatch(&self, response: &str) {
+        debug!("Response: {response}");
+        let Ok(Value::Object(map)) = response.parse::<Value>() else {
+            debug!("Bad response: {response:?}");
+            return;
+        };
+
+        let mut state = self.lock();
+        if let Some(Value::Number(request_id)) = map.get("request_id") {
+            if let Some(tx) = request_id.as_i64().and_then(|id| state.pending.remove(&id)) {
+                tx.send_reply(map);
+            }
+        } else if let Some(Value::String(_)) = map.get("event")
+                && let Ok(event) = serde_json::from_value::<Event>(Value::Object(map)) {
+            state.subscribers.retain(|tx| tx.send_event(&event));
+        }
+    }
+
+    /// Mark the connection as closed once the reader stopped. Dropping the senders wakes up
+    /// everyone still waiting.
+    pub(crate) fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        state.pending.clear();
+        state.subscribers.clear();
+    }
+}
@@ -0,0 +1,172 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::iter::once;
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, mpsc};
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::router::{Router, reply_result};
+use crate::{Error, Event, MpvCommand, number};
+
+type SharedRouter = Router<mpsc::Sender<Map<String, Value>>, mpsc::Sender<Event>>;
+
+struct Inner {
+    writer: Mutex<UnixStream>,
+    router: Arc<SharedRouter>,
+}
+
+impl Inner {
+    fn writer(&self) -> MutexGuard<'_, UnixStream> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Wakes up the reader thread, which then exits
+        let writer = self.writer.get_mut().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.shutdown(Shutdown::Both);
+    }
+}
+
+/// A thread-safe connection to mpv which can be cloned and used from several threads at once.
+///
+/// A background thread reads from the socket, hands replies to the waiting callers by their
+/// `request_id`, and forwards events to every receiver returned by [`SharedMpv::events`]. Commands
+/// therefore never block on a thread which is waiting for events, and vice versa. The connection
+/// is closed once the last handle is dropped.
+///
+/// ```no_run
+/// # use std::thread;
+/// # use mpvc::{Error, Event, SharedMpv};
+/// # fn main() -> Result<(), Error> {
+/// let mpv = SharedMpv::connect("/tmp/mpvsocket")?;
+/// let events = mpv.events();
+/// let handle = mpv.clone();
+/// thread::spawn(move || handle.command("playlist-next"));
+/// for event in events {
+///     println!("{event:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SharedMpv {
+    inner: Arc<Inner>,
+}
+
+impl Debug for SharedMpv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut builder = f.debug_struct("SharedMpv");
+        if let Ok(addr) = self.inner.writer().peer_addr()
+                && let Some(pathname) = addr.as_pathname() {
+            builder.field("path", &pathname);
+        }
+        builder.finish()
+    }
+}
+
+fn read_loop(reader: UnixStream, router: &SharedRouter) {
+    for response in BufReader::new(reader).lines() {
+        let Ok(response) = response else { break; };
+        router.dispatch(&response);
+    }
+    router.close();
+}
+
+impl SharedMpv {
+    /// Connect to the mpv socket located at the given path and start the reader thread.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let writer = UnixStream::connect(path).map_err(Error::ConnectError)?;
+        let reader = writer.try_clone().map_err(Error::ConnectError)?;
+        let router = Arc::new(Router::new());
+        let thread_router = Arc::clone(&router);
+        thread::Builder::new()
+            .name("mpvc-reader".to_owned())
+            .spawn(move || read_loop(reader, &thread_router))
+            .map_err(Error::ConnectError)?;
+        Ok(Self {
+            inner: Arc::new(Inner { writer: Mutex::new(writer), router }),
+        })
+    }
+
+    fn _command(&self, command: Value) -> Result<Value, Error> {
+        let (tx, rx) = mpsc::channel();
+        let (request_id, request) = self.inner.router.register(command.clone(), tx)?;
+        if let Err(e) = self.inner.writer().write_all(request.as_bytes()) {
+            self.inner.router.cancel(request_id);
+            return Err(Error::WriteError(e));
+        }
+        reply_result(rx.recv().ok(), &command)
+    }
+
+    /// Run an mpv command and return its result. See [`Mpv::command_value`](crate::Mpv::command_value).
+    pub fn command_value<I: IntoIterator>(&self, command: &str, args: I) -> Result<Value, Error>
+    where I::Item: Into<Value> {
+        self._command(once(command.into()).chain(args.into_iter().map(Into::into)).collect())
+    }
+
+    /// Run an mpv command, discarding its result.
+    pub fn command_arg<I: IntoIterator>(&self, command: &str, args: I) -> Result<(), Error>
+    where I::Item: Into<Value> {
+        self.command_value(command, args).map(|_| ())
+    }
+
+    /// Run an mpv command without any arguments.
+    pub fn command(&self, command: &str) -> Result<(), Error> {
+        self._command(Value::Array(vec![command.into()])).map(|_| ())
+    }
+
+    /// Run a typed mpv command and return its result. See [`Mpv::execute`](crate::Mpv::execute).
+    pub fn execute(&self, command: &MpvCommand) -> Result<Value, Error> {
+        self._command(command.try_into()?)
+    }
+
+    /// Retrieve a property from mpv.
+    pub fn get_property(&self, property: &str) -> Result<Value, Error> {
+        self._command(Value::Array(vec!["get_property".into(), property.into()]))
+    }
+
+    /// Retrieve a property from mpv and deserialize it into the given type.
+    pub fn get_property_as<T: DeserializeOwned>(&self, property: &str) -> Result<T, Error> {
+        serde_json::from_value(self.get_property(property)?).map_err(Error::JsonError)
+    }
+
+    /// Set an mpv property to the given value.
+    pub fn set_property<T: Into<Value>>(&self, property: &str, value: T) -> Result<(), Error> {
+        self._command(Value::Array(vec!["set_property".into(), property.into(), value.into()])).map(|_| ())
+    }
+
+    /// Add the given value to an mpv property. Runs the 'add' mpv command.
+    pub fn add_property(&self, property: &str, value: f64) -> Result<(), Error> {
+        self._command(Value::Array(vec!["add".into(), property.into(), number(value)?])).map(|_| ())
+    }
+
+    /// Multiply an mpv property by the given value. Runs the 'multiply' mpv command.
+    pub fn multiply_property(&self, property: &str, value: f64) -> Result<(), Error> {
+        self._command(Value::Array(vec!["multiply".into(), property.into(), number(value)?])).map(|_| ())
+    }
+
+    /// Watch a property for changes. Runs the `observe_property` mpv command.
+    pub fn observe_property(&self, id: isize, property: &str) -> Result<(), Error> {
+        self._command(Value::Array(vec!["observe_property".into(), id.into(), property.into()])).map(|_| ())
+    }
+
+    /// Undo the corresponding `observe_property`. Runs the `unobserve_property` mpv command.
+    pub fn unobserve_property(&self, id: isize) -> Result<(), Error> {
+        self._command(Value::Array(vec!["unobserve_property".into(), id.into()])).map(|_| ())
+    }
+
+    /// Subscribe to the events sent by mpv. Every receiver gets all events which arrive after it
+    /// was created, and is disconnected when the connection is closed.
+    pub fn events(&self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.inner.router.subscribe(tx);
+        rx
+    }
+}
@@ -0,0 +1,132 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::iter;
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{Error, Event, Mpv, Observer, Transport};
+
+type Callback = Box<dyn FnMut(&str, &Value) + Send>;
+
+/// A change of a property mirrored by a [`PlayerState`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub property: String,
+    /// The new value, `Value::Null` if the property became unavailable.
+    pub value: Value,
+}
+
+/// A local mirror of a set of mpv properties, kept up to date from their `property-change`
+/// events.
+///
+/// Events are not read by the state itself: either pass every event received from mpv to
+/// [`PlayerState::apply`], or iterate over [`PlayerState::changes`].
+///
+/// ```no_run
+/// # use mpvc::{Error, Mpv, PlayerState};
+/// # fn main() -> Result<(), Error> {
+/// let mut mpv = Mpv::connect("/tmp/mpvsocket")?;
+/// let mut state = PlayerState::new(&mut mpv, ["pause", "media-title"])?;
+/// loop {
+///     let event = mpv.listen_event()?;
+///     if state.apply(&event) {
+///         let pause = state.get_as::<bool>("pause").unwrap_or_default();
+///         let title = state.get_as::<String>("media-title").unwrap_or_default();
+///         println!("{} {title}", if pause { "⏸" } else { "⏵" });
+///     }
+/// }
+/// # }
+/// ```
+pub struct PlayerState {
+    observers: Vec<Observer>,
+    values: BTreeMap<String, Value>,
+    callbacks: Vec<Callback>,
+}
+
+impl Debug for PlayerState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("PlayerState").field("values", &self.values).finish_non_exhaustive()
+    }
+}
+
+impl PlayerState {
+    /// Observe the given properties and wait for their current values, so that the state is
+    /// complete from the start. Events received in the meantime are kept for `listen`.
+    pub fn new<S: Transport, I: IntoIterator>(mpv: &mut Mpv<S>, properties: I) -> Result<Self, Error>
+    where I::Item: AsRef<str> {
+        let observers = properties.into_iter()
+            .map(|property| mpv.observe(property.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut values = BTreeMap::new();
+        for observer in &observers {
+            values.insert(observer.property().to_owned(), mpv.listen_for(observer)?);
+        }
+        Ok(Self { observers, values, callbacks: Vec::new() })
+    }
+
+    /// Call the given function with the property name and new value whenever
+    /// [`PlayerState::apply`] changes the state.
+    pub fn on_change<F: FnMut(&str, &Value) + Send + 'static>(&mut self, callback: F) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Update the state from an event. Returns whether a mirrored property changed; other events
+    /// are ignored.
+    pub fn apply(&mut self, event: &Event) -> bool {
+        let Event::PropertyChange { ref data, .. } = *event else {
+            return false;
+        };
+        let Some(observer) = self.observers.iter().find(|observer| observer.matches(event)) else {
+            return false;
+        };
+        let property = observer.property();
+        if self.values.get(property) == Some(data) {
+            return false;
+        }
+        self.values.insert(property.to_owned(), data.clone());
+        for callback in &mut self.callbacks {
+            callback(property, data);
+        }
+        true
+    }
+
+    /// The current value of a property, or `None` if it is unavailable or not mirrored.
+    pub fn get(&self, property: &str) -> Option<&Value> {
+        self.values.get(property).filter(|value| !value.is_null())
+    }
+
+    /// The current value of a property deserialized into the given type, or `None` if it is
+    /// unavailable, not mirrored, or of a different type.
+    pub fn get_as<T: DeserializeOwned>(&self, property: &str) -> Option<T> {
+        serde_json::from_value(self.get(property)?.clone()).ok()
+    }
+
+    /// A copy of all mirrored values. Unavailable properties are `Value::Null`.
+    pub fn snapshot(&self) -> BTreeMap<String, Value> {
+        self.values.clone()
+    }
+
+    /// Listen for events and yield every change of the state. Other events are discarded, use
+    /// [`PlayerState::apply`] with [`Mpv::listen_event`] to handle them as well.
+    pub fn changes<'a, S: Transport>(&'a mut self, mpv: &'a mut Mpv<S>) -> impl Iterator<Item = Result<Change, Error>> + 'a {
+        let mut failed = false;
+        iter::from_fn(move || {
+            while !failed {
+                match mpv.listen_event() {
+                    Ok(event) if self.apply(&event) => {
+                        if let Event::PropertyChange { name, data, .. } = event {
+                            return Some(Ok(Change { property: name, value: data }));
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            None
+        })
+    }
+}
@@ -0,0 +1,1006 @@
+//! A fake mpv IPC server for tests, available with the `testing` feature.
+//!
+//! [`FakeMpv`] listens on a temporary Unix socket (or an abstract or TCP socket) and implements
+//! enough of mpv's JSON IPC to exercise clients without a real player: properties, property
+//! observers, a playlist, and the events these produce. Commands it doesn't know about can be
+//! scripted with [`FakeMpv::on_command`]. [`Replay`] instead serves a transcript recorded with
+//! [`Mpv::record`](crate::Mpv::record), to reproduce a session without the original player.
+//!
+//! ```no_run
+//! # use mpvc::Error;
+//! # use mpvc::testing::FakeMpv;
+//! # fn main() -> Result<(), Error> {
+//! let fake = FakeMpv::new().map_err(Error::ConnectError)?;
+//! fake.append(["first.flac", "second.flac"]);
+//! let mut mpv = fake.connect()?;
+//! mpv.command("playlist-next")?;
+//! assert_eq!(mpv.get_property_as::<u64>("playlist-pos")?, 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt as _;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use log::debug;
+use serde_json::{Map, Number, Value, json};
+
+use crate::transcript::{Entry, Kind};
+use crate::{Error, LogLevel, Mpv};
+
+/// The reply to a scripted command, see [`FakeMpv::on_command`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reply {
+    /// `None` if no reply is sent at all.
+    result: Option<Result<Value, String>>,
+    events: Vec<Value>,
+}
+
+impl Reply {
+    /// Reply with success and the given data.
+    pub fn ok<T: Into<Value>>(data: T) -> Self {
+        Self { result: Some(Ok(data.into())), events: Vec::new() }
+    }
+
+    /// Reply with the given error, e.g. `"invalid parameter"`.
+    pub fn error<S: Into<String>>(error: S) -> Self {
+        Self { result: Some(Err(error.into())), events: Vec::new() }
+    }
+
+    /// Don't reply at all, like an mpv which hangs.
+    pub fn none() -> Self {
+        Self { result: None, events: Vec::new() }
+    }
+
+    /// Send the given event to all clients right before the reply. This makes it possible to test
+    /// how events which arrive while waiting for a reply are handled.
+    pub fn with_event(mut self, event: Value) -> Self {
+        self.events.push(event);
+        self
+    }
+}
+
+type Handler = Box<dyn FnMut(&[Value]) -> Reply + Send>;
+
+struct Observer {
+    id: Value,
+    name: String,
+    /// The value last reported to the client, `None` until the initial change was sent.
+    last: Option<Option<Value>>,
+}
+
+/// A connection accepted by a fake mpv.
+enum Stream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> IoResult<Self> {
+        match *self {
+            Self::Unix(ref stream) => stream.try_clone().map(Self::Unix),
+            Self::Tcp(ref stream) => stream.try_clone().map(Self::Tcp),
+        }
+    }
+
+    fn shutdown(&self, how: Shutdown) -> IoResult<()> {
+        match *self {
+            Self::Unix(ref stream) => stream.shutdown(how),
+            Self::Tcp(ref stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match *self {
+            Self::Unix(ref mut stream) => stream.read(buf),
+            Self::Tcp(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match *self {
+            Self::Unix(ref mut stream) => stream.write(buf),
+            Self::Tcp(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        match *self {
+            Self::Unix(ref mut stream) => stream.flush(),
+            Self::Tcp(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+/// Where a fake mpv listens.
+#[derive(Debug)]
+enum Endpoint {
+    Path(PathBuf),
+    #[cfg(target_os = "linux")]
+    Abstract(String),
+    Tcp(SocketAddr),
+}
+
+impl Endpoint {
+    /// Connect once, to wake up the listener thread after it was told to exit.
+    fn wake(&self) {
+        match *self {
+            Self::Path(ref path) => drop(UnixStream::connect(path)),
+            #[cfg(target_os = "linux")]
+            Self::Abstract(ref name) => {
+                drop(UnixSocketAddr::from_abstract_name(name).and_then(|addr| UnixStream::connect_addr(&addr)));
+            }
+            Self::Tcp(addr) => drop(TcpStream::connect(addr)),
+        }
+    }
+}
+
+enum Listener {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    fn accept(&self) -> IoResult<Stream> {
+        match *self {
+            Self::Unix(ref listener) => listener.accept().map(|(stream, _)| Stream::Unix(stream)),
+            Self::Tcp(ref listener) => listener.accept().map(|(stream, _)| Stream::Tcp(stream)),
+        }
+    }
+}
+
+struct Client {
+    writer: Stream,
+    observers: Vec<Observer>,
+    /// The level requested with `request_log_messages`.
+    log_level: Option<LogLevel>,
+    /// The `enable_event` and `disable_event` commands received, in order.
+    event_filter: Vec<(String, bool)>,
+}
+
+impl Client {
+    /// Send an event, unless it was disabled.
+    fn send_event(&mut self, event: &Value) {
+        let name = event.get("event").and_then(Value::as_str).unwrap_or_default();
+        let enabled = self.event_filter.iter().rev()
+            .find(|&(filter, _)| filter == name || filter == "all")
+            .is_none_or(|&(_, enabled)| enabled);
+        if enabled {
+            send(&mut self.writer, event);
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    properties: BTreeMap<String, Value>,
+    playlist: Vec<(i64, String)>,
+    current: Option<usize>,
+    next_id: i64,
+    clients: BTreeMap<usize, Client>,
+    handlers: HashMap<String, Handler>,
+    requests: Vec<Value>,
+    /// Events caused by the command being handled, sent after its reply.
+    pending: Vec<Value>,
+    /// Like `pending`, but only sent to a single client.
+    pending_to: Vec<(usize, Value)>,
+    /// The number of connections accepted so far.
+    accepted: usize,
+    closed: bool,
+}
+
+fn send<W: Write>(writer: &mut W, value: &Value) {
+    let _ = writer.write_all((value.to_string() + "\n").as_bytes());
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Number(ref n) => n.as_f64(),
+        Value::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_index(value: &Value) -> Option<usize> {
+    match *value {
+        Value::Number(ref n) => n.as_u64().and_then(|n| usize::try_from(n).ok()),
+        Value::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+impl State {
+    fn broadcast(&mut self, event: &Value) {
+        for client in self.clients.values_mut() {
+            client.send_event(event);
+        }
+    }
+
+    fn property(&self, name: &str) -> Result<Value, &'static str> {
+        let current = self.current.map(|i| &self.playlist[i].1);
+        match name {
+            "playlist" => Ok(self.playlist.iter().enumerate().map(|(i, &(id, ref filename))| {
+                let mut entry = json!({ "filename": filename, "id": id });
+                if Some(i) == self.current {
+                    entry["current"] = true.into();
+                    entry["playing"] = true.into();
+                }
+                entry
+            }).collect()),
+            "playlist-count" => Ok(self.playlist.len().into()),
+            "playlist-pos" => Ok(self.current.map_or(Value::from(-1), Value::from)),
+            "playlist-pos-1" => Ok(self.current.map_or(0, |i| i + 1).into()),
+            "idle-active" => Ok(self.current.is_none().into()),
+            "path" | "filename" => current.map(|f| f.as_str().into()).ok_or("property unavailable"),
+            "media-title" if let Some(title) = self.properties.get(name) => Ok(title.clone()),
+            "media-title" => current.map(|f| f.rsplit('/').next().unwrap_or(f).into()).ok_or("property unavailable"),
+            _ if let Some(key) = name.strip_prefix("metadata/by-key/") => {
+                let metadata = self.properties.get("metadata").and_then(Value::as_object).ok_or("property unavailable")?;
+                metadata.iter()
+                    .find_map(|(k, v)| k.eq_ignore_ascii_case(key).then(|| v.clone()))
+                    .ok_or("property unavailable")
+            }
+            _ => self.properties.get(name).cloned().ok_or("property not found"),
+        }
+    }
+
+    fn set_property(&mut self, name: &str, value: Value) -> Result<(), &'static str> {
+        match name {
+            "playlist-pos" => {
+                let index = value.as_i64().ok_or("unsupported format for accessing property")?;
+                match usize::try_from(index) {
+                    Ok(i) if i < self.playlist.len() => self.play(Some(i)),
+                    Ok(_) => return Err("property unavailable"),
+                    Err(_) => self.play(None),
+                }
+            }
+            "playlist" | "playlist-count" | "playlist-pos-1" | "idle-active" | "path" | "filename" => {
+                return Err("property unavailable");
+            }
+            _ if self.properties.contains_key(name) => {
+                self.properties.insert(name.to_owned(), value);
+            }
+            _ => return Err("property not found"),
+        }
+        Ok(())
+    }
+
+    /// Switch to the given playlist entry, queueing the events mpv would send.
+    fn play(&mut self, index: Option<usize>) {
+        if let Some(i) = self.current.take() {
+            let id = self.playlist[i].0;
+            self.pending.push(json!({ "event": "end-file", "reason": "stop", "playlist_entry_id": id }));
+        }
+        self.current = index;
+        if let Some(i) = index {
+            let id = self.playlist[i].0;
+            self.pending.push(json!({ "event": "start-file", "playlist_entry_id": id }));
+            self.pending.push(json!({ "event": "file-loaded" }));
+        }
+    }
+
+    fn insert(&mut self, index: usize, filename: String) {
+        self.next_id += 1;
+        self.playlist.insert(index, (self.next_id, filename));
+        if let Some(ref mut current) = self.current && *current >= index {
+            *current += 1;
+        }
+    }
+
+    /// Add files to the playlist as `loadfile` and `loadlist` do with the given flags.
+    fn load(&mut self, filenames: Vec<String>, flags: Option<&str>, index: Option<&Value>) -> Result<(), &'static str> {
+        let at = match flags.unwrap_or("replace") {
+            "replace" => {
+                self.play(None);
+                self.playlist.clear();
+                0
+            }
+            "append" | "append-play" => self.playlist.len(),
+            "insert-next" => self.current.map_or(0, |i| i + 1),
+            "insert-at" => {
+                let index = index.ok_or("invalid parameter")?;
+                as_index(index).filter(|&i| i <= self.playlist.len()).unwrap_or(self.playlist.len())
+            }
+            _ => return Err("invalid parameter"),
+        };
+        let added = filenames.len();
+        for (i, filename) in filenames.into_iter().enumerate() {
+            self.insert(at + i, filename);
+        }
+        match flags.unwrap_or("replace") {
+            "replace" if added > 0 => self.play(Some(0)),
+            "append-play" if added > 0 && self.current.is_none() => self.play(Some(at)),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) {
+        if self.current == Some(index) {
+            self.play(None);
+            self.playlist.remove(index);
+            if index < self.playlist.len() {
+                self.play(Some(index));
+            }
+        } else {
+            self.playlist.remove(index);
+            if let Some(ref mut current) = self.current && *current > index {
+                *current -= 1;
+            }
+        }
+    }
+
+    fn handle(&mut self, client: usize, command: &[Value]) -> Result<Value, String> {
+        let Some(name) = command.first().and_then(Value::as_str) else {
+            return Err("invalid parameter".to_owned());
+        };
+        let args = &command[1..];
+        self.builtin(client, name, args).map_err(str::to_owned)
+    }
+
+    fn builtin(&mut self, client: usize, name: &str, args: &[Value]) -> Result<Value, &'static str> {
+        const INVALID: &str = "invalid parameter";
+
+        let property = || args.first().and_then(Value::as_str).ok_or(INVALID);
+        match name {
+            "get_property" => self.property(property()?),
+            "set_property" => {
+                let value = args.get(1).ok_or(INVALID)?.clone();
+                self.set_property(property()?, value).map(|()| Value::Null)
+            }
+            "observe_property" => {
+                let id = args.first().filter(|id| id.is_i64()).ok_or(INVALID)?.clone();
+                let name = args.get(1).and_then(Value::as_str).ok_or(INVALID)?.to_owned();
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.observers.push(Observer { id, name, last: None });
+                }
+                Ok(Value::Null)
+            }
+            "unobserve_property" => {
+                let id = args.first().ok_or(INVALID)?;
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.observers.retain(|observer| observer.id != *id);
+                }
+                Ok(Value::Null)
+            }
+            "add" | "multiply" => {
+                let property = property()?;
+                let current = self.property(property)?.as_f64().ok_or(INVALID)?;
+                let operand = args.get(1).map_or(Some(1.0), as_f64).ok_or(INVALID)?;
+                let value = if name == "add" { current + operand } else { current * operand };
+                self.set_property(property, Number::from_f64(value).ok_or(INVALID)?.into()).map(|()| Value::Null)
+            }
+            "cycle" => {
+                let property = property()?;
+                let value = self.property(property)?.as_bool().ok_or(INVALID)?;
+                self.set_property(property, (!value).into()).map(|()| Value::Null)
+            }
+            "cycle-values" => {
+                let property = property()?;
+                let values = args.get(1..).filter(|values| !values.is_empty()).ok_or(INVALID)?;
+                let current = self.property(property)?;
+                let next = values.iter().position(|v| *v == current).map_or(0, |i| (i + 1) % values.len());
+                self.set_property(property, values[next].clone()).map(|()| Value::Null)
+            }
+            "loadfile" => {
+                let filename = args.first().and_then(Value::as_str).ok_or(INVALID)?.to_owned();
+                self.load(vec![filename], args.get(1).and_then(Value::as_str), args.get(2)).map(|()| Value::Null)
+            }
+            "loadlist" => {
+                // Only plain lists of filenames are understood, with comments as in m3u
+                let path = args.first().and_then(Value::as_str).ok_or(INVALID)?;
+                let list = fs::read_to_string(path).map_err(|_| "error running command")?;
+                let filenames = list.lines().filter(|line| !line.is_empty() && !line.starts_with('#')).map(str::to_owned).collect();
+                self.load(filenames, args.get(1).and_then(Value::as_str), args.get(2)).map(|()| Value::Null)
+            }
+            "playlist-next" | "playlist-prev" => {
+                let next = match (name, self.current) {
+                    ("playlist-next", Some(i)) if i + 1 < self.playlist.len() => i + 1,
+                    ("playlist-prev", Some(i)) if i > 0 => i - 1,
+                    _ => return Err("error running command"),
+                };
+                self.play(Some(next));
+                Ok(Value::Null)
+            }
+            "playlist-play-index" => {
+                match args.first().ok_or(INVALID)? {
+                    Value::String(s) if s == "current" => self.play(self.current),
+                    Value::String(s) if s == "none" => self.play(None),
+                    index => {
+                        let i = as_index(index).filter(|&i| i < self.playlist.len()).ok_or(INVALID)?;
+                        self.play(Some(i));
+                    }
+                }
+                Ok(Value::Null)
+            }
+            "playlist-move" => {
+                let from = args.first().and_then(as_index).filter(|&i| i < self.playlist.len()).ok_or(INVALID)?;
+                let to = args.get(1).and_then(as_index).filter(|&i| i <= self.playlist.len()).ok_or(INVALID)?;
+                let to = if from < to { to - 1 } else { to };
+                let entry = self.playlist.remove(from);
+                self.playlist.insert(to, entry);
+                if let Some(ref mut current) = self.current {
+                    if *current == from {
+                        *current = to;
+                    } else {
+                        if *current > from {
+                            *current -= 1;
+                        }
+                        if *current >= to {
+                            *current += 1;
+                        }
+                    }
+                }
+                Ok(Value::Null)
+            }
+            "playlist-remove" => {
+                let index = match args.first() {
+                    None => self.current,
+                    Some(Value::String(s)) if s == "current" => self.current,
+                    Some(index) => as_index(index),
+                };
+                let index = index.filter(|&i| i < self.playlist.len()).ok_or(INVALID)?;
+                self.remove(index);
+                Ok(Value::Null)
+            }
+            "playlist-clear" => {
+                // Like mpv, keep the entry which is currently playing
+                let current = self.current.map(|i| self.playlist[i].clone());
+                self.playlist = current.into_iter().collect();
+                self.current = self.current.map(|_| 0);
+                Ok(Value::Null)
+            }
+            "stop" => {
+                self.play(None);
+                self.playlist.clear();
+                Ok(Value::Null)
+            }
+            "seek" => {
+                let target = args.first().and_then(as_f64).ok_or(INVALID)?;
+                if self.current.is_none() {
+                    return Err("error running command");
+                }
+                if let Some(time) = self.properties.get("time-pos").and_then(Value::as_f64) {
+                    let time = match args.get(1).and_then(Value::as_str).unwrap_or("relative") {
+                        "absolute" => target,
+                        _ => time + target,
+                    };
+                    self.properties.insert("time-pos".to_owned(), Number::from_f64(time.max(0.0)).ok_or(INVALID)?.into());
+                }
+                self.pending.push(json!({ "event": "seek" }));
+                self.pending.push(json!({ "event": "playback-restart" }));
+                Ok(Value::Null)
+            }
+            "request_log_messages" => {
+                let level = match args.first().ok_or(INVALID)? {
+                    level if level == "no" => None,
+                    level => Some(serde_json::from_value(level.clone()).map_err(|_| INVALID)?),
+                };
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.log_level = level;
+                }
+                Ok(Value::Null)
+            }
+            "enable_event" | "disable_event" => {
+                let event = args.first().and_then(Value::as_str).ok_or(INVALID)?.to_owned();
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.event_filter.push((event, name == "enable_event"));
+                }
+                Ok(Value::Null)
+            }
+            "client_name" => Ok(format!("ipc-{client}").into()),
+            "script-message" => {
+                self.pending.push(json!({ "event": "client-message", "args": args }));
+                Ok(Value::Null)
+            }
+            "script-message-to" => {
+                let target = args.first().and_then(Value::as_str).ok_or(INVALID)?;
+                let client = target.strip_prefix("ipc-").and_then(|id| id.parse().ok())
+                    .filter(|id| self.clients.contains_key(id))
+                    .ok_or("error running command")?;
+                self.pending_to.push((client, json!({ "event": "client-message", "args": args[1..] })));
+                Ok(Value::Null)
+            }
+            "quit" => {
+                self.pending.push(json!({ "event": "shutdown" }));
+                self.closed = true;
+                Ok(Value::Null)
+            }
+            _ => Err(INVALID),
+        }
+    }
+
+    /// Send the queued events, followed by a `property-change` event for every observed property
+    /// whose value changed.
+    fn flush(&mut self) {
+        for event in mem::take(&mut self.pending) {
+            self.broadcast(&event);
+        }
+        for (client, event) in mem::take(&mut self.pending_to) {
+            if let Some(client) = self.clients.get_mut(&client) {
+                client.send_event(&event);
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (&client, state) in &self.clients {
+            for (i, observer) in state.observers.iter().enumerate() {
+                let value = self.property(&observer.name).ok();
+                if observer.last.as_ref() != Some(&value) {
+                    changes.push((client, i, value));
+                }
+            }
+        }
+        for (client, i, value) in changes {
+            let Some(client) = self.clients.get_mut(&client) else { continue; };
+            let observer = &mut client.observers[i];
+            let mut event = Map::new();
+            event.insert("event".to_owned(), "property-change".into());
+            event.insert("id".to_owned(), observer.id.clone());
+            event.insert("name".to_owned(), observer.name.as_str().into());
+            if let Some(ref data) = value {
+                event.insert("data".to_owned(), data.clone());
+            }
+            observer.last = Some(value);
+            client.send_event(&Value::Object(event));
+        }
+
+        if self.closed {
+            for client in self.clients.values() {
+                let _ = client.writer.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+/// How long [`FakeMpv::connect`] waits for the connection to be accepted.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A name which no other fake mpv uses.
+fn unique_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("mpvc-fake-{}-{n}", process::id())
+}
+
+/// Listen on a new socket in the temporary directory.
+fn bind_temp() -> IoResult<(PathBuf, UnixListener)> {
+    let path = std::env::temp_dir().join(unique_name() + ".sock");
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    Ok((path, listener))
+}
+
+struct Shared {
+    state: Mutex<State>,
+    received: Condvar,
+    accepted: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Block until the condition holds, or the timeout has passed. Returns whether it holds.
+    fn wait_until<F: Fn(&State) -> bool>(&self, condvar: &Condvar, timeout: Duration, condition: F) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if condition(&state) {
+                return true;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            state = condvar.wait_timeout(state, remaining).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+}
+
+fn serve(shared: &Shared, id: usize, stream: Stream) {
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break; };
+        let Ok(Value::Object(request)) = line.parse::<Value>() else { continue; };
+        let request_id = request.get("request_id").cloned().unwrap_or(0.into());
+        let command = request.get("command").and_then(Value::as_array).cloned().unwrap_or_default();
+
+        let mut state = shared.lock();
+        state.requests.push(Value::Array(command.clone()));
+        shared.received.notify_all();
+
+        let name = command.first().and_then(Value::as_str).unwrap_or_default().to_owned();
+        let result = if let Some(handler) = state.handlers.get_mut(&name) {
+            let reply = handler(&command[1..]);
+            for event in &reply.events {
+                state.broadcast(event);
+            }
+            reply.result
+        } else {
+            Some(state.handle(id, &command))
+        };
+
+        let reply = result.map(|result| match result {
+            Ok(data) => json!({ "request_id": request_id, "error": "success", "data": data }),
+            Err(error) => json!({ "request_id": request_id, "error": error }),
+        });
+        if let Some(ref reply) = reply && let Some(client) = state.clients.get_mut(&id) {
+            send(&mut client.writer, reply);
+        }
+        state.flush();
+    }
+    shared.lock().clients.remove(&id);
+}
+
+/// A fake mpv listening on a temporary Unix socket, which is removed when this is dropped.
+/// [`FakeMpv::new_abstract`] and [`FakeMpv::new_tcp`] listen on an abstract or TCP socket instead.
+///
+/// Initially, the playlist is empty, and `pause`, `mute`, `volume`, `speed`, `loop-file` and
+/// `loop-playlist` are set to mpv's defaults. Other properties only exist once they are set with
+/// [`FakeMpv::set_property`].
+pub struct FakeMpv {
+    endpoint: Endpoint,
+    shared: Arc<Shared>,
+}
+
+impl Debug for FakeMpv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("FakeMpv").field("endpoint", &self.endpoint).finish_non_exhaustive()
+    }
+}
+
+impl Drop for FakeMpv {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        for client in state.clients.values() {
+            let _ = client.writer.shutdown(Shutdown::Both);
+        }
+        drop(state);
+        // Wake up the listener thread, which then exits
+        self.endpoint.wake();
+        if let Endpoint::Path(ref path) = self.endpoint {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl FakeMpv {
+    /// Start a fake mpv on a new socket in the temporary directory.
+    pub fn new() -> IoResult<Self> {
+        let (path, listener) = bind_temp()?;
+        Self::start(Endpoint::Path(path), Listener::Unix(listener))
+    }
+
+    /// Start a fake mpv on a new socket in the abstract namespace, like one started with
+    /// `--input-ipc-server=@name`. See [`FakeMpv::abstract_name`].
+    #[cfg(target_os = "linux")]
+    pub fn new_abstract() -> IoResult<Self> {
+        let name = unique_name();
+        let listener = UnixListener::bind_addr(&UnixSocketAddr::from_abstract_name(&name)?)?;
+        Self::start(Endpoint::Abstract(name), Listener::Unix(listener))
+    }
+
+    /// Start a fake mpv on a TCP socket on a free port of the loopback interface, like a relay of
+    /// mpv's socket. See [`FakeMpv::tcp_addr`].
+    pub fn new_tcp() -> IoResult<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        Self::start(Endpoint::Tcp(listener.local_addr()?), Listener::Tcp(listener))
+    }
+
+    fn start(endpoint: Endpoint, listener: Listener) -> IoResult<Self> {
+        let properties = [
+            ("pause", false.into()),
+            ("mute", false.into()),
+            ("volume", 100.0.into()),
+            ("speed", 1.0.into()),
+            ("loop-file", false.into()),
+            ("loop-playlist", false.into()),
+        ];
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                properties: properties.into_iter().map(|(k, v): (&str, Value)| (k.to_owned(), v)).collect(),
+                ..State::default()
+            }),
+            received: Condvar::new(),
+            accepted: Condvar::new(),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        thread::Builder::new().name("mpvc-fake".to_owned()).spawn(move || {
+            for id in 0.. {
+                let stream = listener.accept();
+                let mut state = thread_shared.lock();
+                if state.closed {
+                    break;
+                }
+                let Ok(stream) = stream else { continue; };
+                let Ok(writer) = stream.try_clone() else { continue; };
+                state.clients.insert(id, Client { writer, observers: Vec::new(), log_level: None, event_filter: Vec::new() });
+                state.accepted += 1;
+                thread_shared.accepted.notify_all();
+                drop(state);
+                let shared = Arc::clone(&thread_shared);
+                thread::spawn(move || serve(&shared, id, stream));
+            }
+        })?;
+
+        Ok(Self { endpoint, shared })
+    }
+
+    /// The path of the socket, which is empty unless the fake mpv was started with
+    /// [`FakeMpv::new`].
+    pub fn path(&self) -> &Path {
+        match self.endpoint {
+            Endpoint::Path(ref path) => path,
+            _ => Path::new(""),
+        }
+    }
+
+    /// The name of the socket in the abstract namespace, if the fake mpv was started with
+    /// [`FakeMpv::new_abstract`].
+    #[cfg(target_os = "linux")]
+    pub fn abstract_name(&self) -> Option<&str> {
+        match self.endpoint {
+            Endpoint::Abstract(ref name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The address of the socket, if the fake mpv was started with [`FakeMpv::new_tcp`].
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        match self.endpoint {
+            Endpoint::Tcp(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Connect a new client. Returns once the fake mpv has accepted the connection, so that
+    /// events sent afterwards reach the client. Clients of a fake mpv listening on a TCP socket
+    /// have to connect with [`Mpv::connect_tcp`] instead.
+    pub fn connect(&self) -> Result<Mpv, Error> {
+        let accepted = self.shared.lock().accepted;
+        let mpv = match self.endpoint {
+            Endpoint::Path(ref path) => Mpv::connect(path)?,
+            #[cfg(target_os = "linux")]
+            Endpoint::Abstract(ref name) => Mpv::connect_abstract(name)?,
+            Endpoint::Tcp(_) => {
+                return Err(Error::ConnectError(IoError::new(IoErrorKind::Unsupported, "not a Unix socket")));
+            }
+        };
+        if !self.shared.wait_until(&self.shared.accepted, CONNECT_TIMEOUT, |state| state.accepted > accepted) {
+            return Err(Error::Timeout);
+        }
+        Ok(mpv)
+    }
+
+    /// The number of connected clients.
+    pub fn clients(&self) -> usize {
+        self.shared.lock().clients.len()
+    }
+
+    /// Block until the given number of clients is connected, for clients which connected to the
+    /// fake mpv themselves. Returns `false` if that didn't happen within the timeout.
+    pub fn wait_for_clients(&self, clients: usize, timeout: Duration) -> bool {
+        self.shared.wait_until(&self.shared.accepted, timeout, |state| state.clients.len() >= clients)
+    }
+
+    /// Close the connections of all clients, as if mpv was restarted. Clients can connect again,
+    /// but like with a new mpv, what they observed or requested is forgotten.
+    pub fn disconnect(&self) {
+        for client in self.shared.lock().clients.values() {
+            let _ = client.writer.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Set a property, notifying the clients observing it.
+    pub fn set_property<T: Into<Value>>(&self, property: &str, value: T) {
+        let value = value.into();
+        let mut state = self.shared.lock();
+        if state.set_property(property, value.clone()) == Err("property not found") {
+            state.properties.insert(property.to_owned(), value);
+        }
+        state.flush();
+    }
+
+    /// The current value of a property, as a client would retrieve it.
+    pub fn get_property(&self, property: &str) -> Option<Value> {
+        self.shared.lock().property(property).ok()
+    }
+
+    /// Append files to the playlist. Playback starts with the first one if nothing is playing.
+    pub fn append<I: IntoIterator>(&self, files: I)
+    where I::Item: Into<String> {
+        let mut state = self.shared.lock();
+        for file in files {
+            let len = state.playlist.len();
+            state.insert(len, file.into());
+        }
+        if state.current.is_none() && !state.playlist.is_empty() {
+            state.play(Some(0));
+        }
+        state.flush();
+    }
+
+    /// The filenames in the playlist.
+    pub fn playlist(&self) -> Vec<String> {
+        self.shared.lock().playlist.iter().map(|(_, filename)| filename.clone()).collect()
+    }
+
+    /// Log a message, which is sent to the clients which requested log messages of its level.
+    pub fn log(&self, level: LogLevel, prefix: &str, text: &str) {
+        let event = json!({ "event": "log-message", "prefix": prefix, "level": level.as_str(), "text": text });
+        for client in self.shared.lock().clients.values_mut() {
+            if client.log_level.is_some_and(|requested| level <= requested) {
+                client.send_event(&event);
+            }
+        }
+    }
+
+    /// Send an event to all clients.
+    pub fn emit(&self, event: Value) {
+        self.shared.lock().broadcast(&event);
+    }
+
+    /// Answer the given command with a handler instead of the built-in implementation. The handler
+    /// is passed the arguments of the command.
+    ///
+    /// ```no_run
+    /// # use mpvc::Error;
+    /// # use mpvc::testing::{FakeMpv, Reply};
+    /// # use serde_json::json;
+    /// # fn main() -> Result<(), Error> {
+    /// let fake = FakeMpv::new().map_err(Error::ConnectError)?;
+    /// fake.on_command("expand-path", |args| {
+    ///     Reply::ok(args[0].as_str().unwrap().replace("~~", "/home/user/.config/mpv"))
+    ///         .with_event(json!({ "event": "seek" }))
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_command<F: FnMut(&[Value]) -> Reply + Send + 'static>(&self, command: &str, handler: F) {
+        self.shared.lock().handlers.insert(command.to_owned(), Box::new(handler));
+    }
+
+    /// All commands received so far, in order.
+    pub fn requests(&self) -> Vec<Value> {
+        self.shared.lock().requests.clone()
+    }
+
+    /// Block until a command with the given name was received. Returns `false` if that didn't
+    /// happen within the timeout.
+    pub fn wait_for_command(&self, command: &str, timeout: Duration) -> bool {
+        self.shared.wait_until(&self.shared.received, timeout, |state| {
+            state.requests.iter().any(|r| r.get(0).and_then(Value::as_str) == Some(command))
+        })
+    }
+}
+
+fn replay(entries: &[Entry], stream: UnixStream) {
+    let Ok(mut writer) = stream.try_clone() else { return; };
+    let mut requests = BufReader::new(stream).lines();
+    let mut ids = HashMap::new();
+    for entry in entries {
+        match entry.kind {
+            Kind::Request => {
+                let Some(Ok(line)) = requests.next() else { return; };
+                let request = line.parse::<Value>().unwrap_or_default();
+                let expected = entry.message.get("command").unwrap_or(&Value::Null);
+                if request.get("command") != Some(expected) {
+                    // Serving the recorded reply anyway would only make the client fail later on,
+                    // in a confusing way, so reject the request and end the session
+                    debug!("Replay: expected {}, received {line}", entry.message);
+                    let error = format!("replay expected the command {expected}");
+                    send(&mut writer, &json!({ "request_id": request.get("request_id"), "error": error }));
+                    return;
+                }
+                if let Some(id) = entry.message.get("request_id") {
+                    ids.insert(id.to_string(), request.get("request_id").cloned().unwrap_or_default());
+                }
+            }
+            Kind::Reply => {
+                let mut reply = entry.message.clone();
+                if let Some(id) = reply.get("request_id").and_then(|id| ids.get(&id.to_string())) {
+                    reply["request_id"] = id.clone();
+                }
+                send(&mut writer, &reply);
+            }
+            Kind::Event => send(&mut writer, &entry.message),
+        }
+    }
+}
+
+/// A fake mpv which serves a transcript recorded with [`Mpv::record`](crate::Mpv::record) on a
+/// temporary Unix socket, which is removed when this is dropped.
+///
+/// Every client is played the whole transcript: each recorded request waits for the client to
+/// send its next request, which is answered with the recorded reply, while events are sent as soon
+/// as they come up. The connection is closed once the transcript is exhausted. If the client sends
+/// a different command than the one recorded, it is answered with an error instead and the
+/// connection is closed right away.
+///
+/// ```no_run
+/// # use mpvc::Error;
+/// # use mpvc::testing::Replay;
+/// # fn main() -> Result<(), Error> {
+/// let replay = Replay::open("transcript.ndjson").map_err(Error::ConnectError)?;
+/// let mut mpv = replay.connect()?;
+/// println!("{}", mpv.get_property("playlist-count")?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Replay {
+    path: PathBuf,
+    closed: Arc<AtomicBool>,
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // Wake up the listener thread, which then exits
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Replay {
+    /// Serve the transcript read from the given reader.
+    pub fn new<R: BufRead>(transcript: R) -> IoResult<Self> {
+        let entries = transcript.lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| serde_json::from_str::<Entry>(&line?).map_err(|e| IoError::new(IoErrorKind::InvalidData, e)))
+            .collect::<IoResult<Arc<[Entry]>>>()?;
+        let (path, listener) = bind_temp()?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let thread_closed = Arc::clone(&closed);
+        thread::Builder::new().name("mpvc-replay".to_owned()).spawn(move || {
+            for stream in listener.incoming() {
+                if thread_closed.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue; };
+                let entries = Arc::clone(&entries);
+                thread::spawn(move || replay(&entries, stream));
+            }
+        })?;
+        Ok(Self { path, closed })
+    }
+
+    /// Serve the transcript stored in the file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> IoResult<Self> {
+        Self::new(BufReader::new(fs::File::open(path)?))
+    }
+
+    /// The path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Connect a new client.
+    pub fn connect(&self) -> Result<Mpv, Error> {
+        Mpv::connect(&self.path)
+    }
+}
@@ -0,0 +1,53 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The kind of a message in a transcript.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Kind {
+    Request,
+    Reply,
+    Event,
+}
+
+/// A line of a transcript: a message sent to or received from mpv, along with the time in seconds
+/// since the Unix epoch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    pub time: f64,
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    pub message: Value,
+}
+
+/// Writes the messages exchanged with mpv as NDJSON, one [`Entry`] per line.
+pub(crate) struct Recorder {
+    writer: Box<dyn Write + Send>,
+}
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self { writer: Box::new(writer) }
+    }
+
+    /// Record a line sent to mpv, or received from it.
+    pub fn record(&mut self, line: &str, sent: bool) {
+        let message = line.parse::<Value>().unwrap_or_else(|_| line.into());
+        let kind = if sent {
+            Kind::Request
+        } else if message.get("request_id").is_some() {
+            Kind::Reply
+        } else {
+            Kind::Event
+        };
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        let entry = serde_json::to_string(&Entry { time, kind, message }).unwrap_or_default();
+        if let Err(e) = self.writer.write_all((entry + "\n").as_bytes()).and_then(|()| self.writer.flush()) {
+            debug!("Failed to record: {e}");
+        }
+    }
+}
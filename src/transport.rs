@@ -0,0 +1,44 @@
+use core::time::Duration;
+use std::io::{Read, Result as IoResult, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+
+/// A byte stream which carries the JSON IPC protocol, e.g. a socket connected to mpv or a relay
+/// in front of it.
+///
+/// Timeouts are optional: transports which can't enforce them keep the default implementations,
+/// in which case reads and writes may block past the timeout set with
+/// [`Mpv::set_timeout`](crate::Mpv::set_timeout).
+pub trait Transport: Read + Write {
+    /// Set the timeout for subsequent reads. `None` blocks indefinitely.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        let _ = timeout;
+        Ok(())
+    }
+
+    /// Set the timeout for subsequent writes. `None` blocks indefinitely.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        let _ = timeout;
+        Ok(())
+    }
+}
+
+impl Transport for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        Self::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        Self::set_write_timeout(self, timeout)
+    }
+}
+
+impl Transport for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        Self::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> IoResult<()> {
+        Self::set_write_timeout(self, timeout)
+    }
+}
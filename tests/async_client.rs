@@ -0,0 +1,92 @@
+#![cfg(feature = "tokio")]
+
+use core::time::Duration;
+use std::sync::Arc;
+
+use mpvc::testing::{FakeMpv, Reply};
+use mpvc::{AsyncMpv, Error, Event};
+use serde_json::json;
+use tokio_stream::StreamExt as _;
+
+fn fake() -> FakeMpv {
+    FakeMpv::new().unwrap()
+}
+
+#[tokio::test]
+async fn concurrent_commands() {
+    let fake = fake();
+    for i in 0..8 {
+        fake.set_property(&format!("user-data/{i}"), 0);
+    }
+    let mpv = Arc::new(AsyncMpv::connect(fake.path()).await.unwrap());
+    let tasks = (0..8).map(|i| {
+        let mpv = Arc::clone(&mpv);
+        tokio::spawn(async move {
+            let property = format!("user-data/{i}");
+            for n in 0..25 {
+                mpv.set_property(&property, n).await.unwrap();
+                // Each task gets the reply to its own request
+                assert_eq!(mpv.get_property_as::<i64>(&property).await.unwrap(), n);
+            }
+        })
+    }).collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn replies_routed_by_request_id() {
+    let fake = fake();
+    let mpv = AsyncMpv::connect(fake.path()).await.unwrap();
+    let (volume, mute, speed) = tokio::join!(
+        mpv.get_property("volume"),
+        mpv.get_property("mute"),
+        mpv.get_property("speed"),
+    );
+    assert_eq!(volume.unwrap(), 100.0);
+    assert_eq!(mute.unwrap(), false);
+    assert_eq!(speed.unwrap(), 1.0);
+    let Err(Error::MpvError(e)) = mpv.get_property("no-such-property").await else { panic!() };
+    assert_eq!(e.command, json!(["get_property", "no-such-property"]));
+}
+
+#[tokio::test]
+async fn events() {
+    let fake = fake();
+    fake.on_command("expand-path", |args| {
+        Reply::ok(args[0].clone())
+            .with_event(json!({ "event": "seek" }))
+            .with_event(json!({ "event": "playback-restart" }))
+    });
+    let mpv = AsyncMpv::connect(fake.path()).await.unwrap();
+    let mut events = mpv.events();
+    let mut other = mpv.events();
+    assert_eq!(mpv.command_value("expand-path", ["~~/input.conf"]).await.unwrap(), "~~/input.conf");
+    mpv.observe_property(1, "pause").await.unwrap();
+    for events in [&mut events, &mut other] {
+        assert_eq!(events.next().await, Some(Event::Seek));
+        assert_eq!(events.next().await, Some(Event::PlaybackRestart));
+        assert_eq!(events.next().await, Some(Event::PropertyChange { id: 1, name: "pause".to_owned(), data: false.into() }));
+    }
+}
+
+#[tokio::test]
+async fn errors_after_disconnect() {
+    let fake = fake();
+    fake.on_command("hang", |_| Reply::none());
+    let mpv = Arc::new(AsyncMpv::connect(fake.path()).await.unwrap());
+    let mut events = mpv.events();
+    let handle = Arc::clone(&mpv);
+    let pending = tokio::spawn(async move { handle.command("hang").await });
+    tokio::task::spawn_blocking(move || {
+        assert!(fake.wait_for_command("hang", Duration::from_secs(5)));
+        drop(fake);
+    }).await.unwrap();
+
+    // The command in flight, the event streams and later commands all fail
+    assert!(matches!(pending.await.unwrap(), Err(Error::ReadError(_))));
+    assert_eq!(events.next().await, None);
+    assert!(matches!(mpv.command("stop").await, Err(Error::ReadError(_))));
+    assert_eq!(mpv.events().next().await, None);
+}
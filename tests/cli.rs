@@ -0,0 +1,297 @@
+use core::time::Duration;
+use std::process::{Command, Output};
+use std::thread;
+
+use mpvc::{Event, LogLevel};
+use mpvc::testing::{FakeMpv, Replay, Reply};
+use serde_json::json;
+
+fn mpvc(fake: &FakeMpv, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .arg("--socket")
+        .arg(fake.path())
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+fn stdout(fake: &FakeMpv, args: &[&str]) -> String {
+    let output = mpvc(fake, args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn playlist() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["/music/a.flac", "/music/b.flac"]);
+    assert_eq!(stdout(&fake, &["playlist"]), "1\t/music/a.flac\n2\t/music/b.flac\n");
+}
+
+#[test]
+fn add_and_reverse() {
+    let fake = FakeMpv::new().unwrap();
+    stdout(&fake, &["add", "a", "b", "c"]);
+    assert_eq!(fake.playlist(), ["a", "b", "c"]);
+    stdout(&fake, &["reverse"]);
+    assert_eq!(fake.playlist(), ["c", "b", "a"]);
+    stdout(&fake, &["clear"]);
+    assert_eq!(fake.playlist(), ["a"]);
+}
+
+#[test]
+fn deprecated_add_mode() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["a", "b"]);
+    let output = mpvc(&fake, &["add", "--mode", "append-next", "c"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "mpvc: --mode append-next is deprecated, use insert-next instead\n");
+    assert_eq!(fake.playlist(), ["a", "c", "b"]);
+}
+
+#[test]
+fn seek_position() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["a"]);
+    fake.set_property("time-pos", 10.0);
+    stdout(&fake, &["seek", "--mode", "absolute", "1:05.5"]);
+    assert_eq!(fake.get_property("time-pos"), Some(65.5.into()));
+    for target in ["inf", "-inf", "nan", "1:inf"] {
+        assert_eq!(mpvc(&fake, &["seek", "--", target]).status.code(), Some(2));
+    }
+    assert_eq!(fake.get_property("time-pos"), Some(65.5.into()));
+}
+
+#[test]
+fn format() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["/music/song.flac"]);
+    fake.set_property("metadata", json!({ "artist": "Someone", "ALBUM": "Something" }));
+    fake.set_property("time-pos", 83.5);
+    fake.set_property("duration", 3725.0);
+    let output = stdout(&fake, &["format", "%artist% - %title% [%time%/%duration%] %pause?paused:playing%[ %missing%]%n%"]);
+    assert_eq!(output, "Someone - song.flac 01:23/01:02:05 playing\n");
+    // Metadata keys are matched exactly
+    assert_eq!(stdout(&fake, &["format", "[%album%][%ALBUM%]"]), "Something");
+}
+
+#[test]
+fn toggle_and_volume() {
+    let fake = FakeMpv::new().unwrap();
+    stdout(&fake, &["toggle"]);
+    assert_eq!(fake.get_property("pause"), Some(true.into()));
+    stdout(&fake, &["volume", "--mode", "relative", "--", "-10"]);
+    assert_eq!(fake.get_property("volume"), Some(90.0.into()));
+    assert_eq!(stdout(&fake, &["get", "volume"]), "90.0\n");
+}
+
+#[test]
+fn wait_for_property() {
+    let fake = FakeMpv::new().unwrap();
+    let path = fake.path().to_owned();
+    let child = thread::spawn(move || {
+        Command::new(env!("CARGO_BIN_EXE_mpvc")).arg("--socket").arg(path).args(["wait", "--", "pause"]).status().unwrap()
+    });
+    assert!(fake.wait_for_command("observe_property", Duration::from_secs(5)));
+    fake.set_property("pause", true);
+    assert!(child.join().unwrap().success());
+}
+
+#[test]
+fn wait_for_event() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["a", "b"]);
+    let path = fake.path().to_owned();
+    let child = thread::spawn(move || {
+        Command::new(env!("CARGO_BIN_EXE_mpvc")).arg("--socket").arg(path).args(["wait", "file-loaded"]).status().unwrap()
+    });
+    // The client sends nothing before waiting, so wait for it to connect
+    while fake.clients() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    fake.emit(json!({ "event": "seek" }));
+    fake.emit(json!({ "event": "file-loaded" }));
+    assert!(child.join().unwrap().success());
+}
+
+#[test]
+fn record_and_replay() {
+    let fake = FakeMpv::new().unwrap();
+    fake.append(["a", "b"]);
+    let transcript = fake.path().with_extension("ndjson");
+    let recorded = stdout(&fake, &["--record", transcript.to_str().unwrap(), "playlist"]);
+    drop(fake);
+
+    let replay = Replay::open(&transcript).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .arg("--socket")
+        .arg(replay.path())
+        .arg("playlist")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    std::fs::remove_file(transcript).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), recorded);
+}
+
+#[test]
+fn dry_run() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .args(["--socket", "/nonexistent", r#"--dry-run={"playlist-count": 3}"#, "reverse"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"{"command":["get_property","playlist-count"],"request_id":0}"#, "\n",
+        r#"{"command":["playlist-move",2,0],"request_id":1}"#, "\n",
+        r#"{"command":["playlist-move",2,1],"request_id":2}"#, "\n",
+    ));
+
+    // The latest mpv is assumed, instead of printing the commands to find out its version
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .args(["--socket", "/nonexistent", "--dry-run", "add", "--mode", "insert-next", "a"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"{"command":["loadfile","a","insert-next"],"request_id":0}"#, "\n",
+    ));
+
+    // Commands which wait for events stop once their setup is printed
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .args(["--socket", "/nonexistent", "--dry-run", "wait", "--", "pause"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"{"command":["observe_property",1073741824,"pause"],"request_id":0}"#, "\n",
+    ));
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc"))
+        .args(["--socket", "/nonexistent", "--dry-run", "log", "--level", "warn"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"{"command":["request_log_messages","warn"],"request_id":0}"#, "\n",
+    ));
+}
+
+#[test]
+fn exit_codes() {
+    let fake = FakeMpv::new().unwrap();
+    assert_eq!(mpvc(&fake, &["get", "path"]).status.code(), Some(6));
+    assert_eq!(mpvc(&fake, &["get", "no-such-property"]).status.code(), Some(7));
+    assert_eq!(mpvc(&fake, &["run", "no-such-command"]).status.code(), Some(8));
+    assert_eq!(mpvc(&fake, &["next"]).status.code(), Some(5));
+    fake.append(["a"]);
+    fake.set_property("metadata", json!({}));
+    let output = mpvc(&fake, &["metadata", "artist"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "mpvc: the current file has no metadata key 'artist'\n");
+    assert!(mpvc(&fake, &["--quiet", "get", "path-typo"]).stderr.is_empty());
+    let output = mpvc(&fake, &["set", "--json", "volume", "{50"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("mpvc: invalid JSON '{50': "));
+    assert_eq!(mpvc(&fake, &["run", "loadfile", "--", "[1,"]).status.code(), Some(2));
+    drop(fake);
+    let output = Command::new(env!("CARGO_BIN_EXE_mpvc")).args(["--socket", "/nonexistent", "play"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--input-ipc-server=/nonexistent"));
+}
+
+#[test]
+fn messages() {
+    let fake = FakeMpv::new().unwrap();
+    let path = fake.path().to_owned();
+    let child = thread::spawn(move || {
+        Command::new(env!("CARGO_BIN_EXE_mpvc")).arg("--socket").arg(path).args(["message", "listen", "greet"]).output().unwrap()
+    });
+    assert!(fake.wait_for_command("client_name", Duration::from_secs(5)));
+    let mut mpv = fake.connect().unwrap();
+    mpv.script_message(["other", "x"]).unwrap();
+    mpv.script_message(["greet", "hello", "world"]).unwrap();
+    fake.emit(json!({ "event": "shutdown" }));
+    let output = child.join().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "greet\thello\tworld\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "mpvc: listening as ipc-0\n");
+
+    let mut receiver = fake.connect().unwrap();
+    let name = receiver.client_name().unwrap();
+    stdout(&fake, &["message", "send", &name, "ping", "-1"]);
+    assert_eq!(receiver.listen_event().unwrap(), Event::ClientMessage { args: vec!["ping".to_owned(), "-1".to_owned()] });
+    assert_eq!(mpvc(&fake, &["message", "send", "no-such-client"]).status.code(), Some(5));
+}
+
+#[test]
+fn log() {
+    let fake = FakeMpv::new().unwrap();
+    let path = fake.path().to_owned();
+    let child = thread::spawn(move || {
+        Command::new(env!("CARGO_BIN_EXE_mpvc"))
+            .arg("--socket")
+            .arg(path)
+            .args(["log", "--level", "warn", "--prefix", "ytdl_hook"])
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    });
+    assert!(fake.wait_for_command("request_log_messages", Duration::from_secs(5)));
+    fake.log(LogLevel::Error, "ytdl_hook", "youtube-dl failed\n");
+    fake.log(LogLevel::Info, "ytdl_hook", "not shown\n");
+    fake.log(LogLevel::Error, "cplayer", "not shown\n");
+    fake.emit(json!({ "event": "shutdown" }));
+    let output = child.join().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[ytdl_hook] youtube-dl failed\n");
+}
+
+#[test]
+fn observe_events() {
+    let fake = FakeMpv::new().unwrap();
+    let path = fake.path().to_owned();
+    let child = thread::spawn(move || {
+        Command::new(env!("CARGO_BIN_EXE_mpvc")).arg("--socket").arg(path).args(["observe", "--events", "seek", "pause"]).output().unwrap()
+    });
+    assert!(fake.wait_for_command("observe_property", Duration::from_secs(5)));
+    fake.emit(json!({ "event": "file-loaded" }));
+    fake.emit(json!({ "event": "seek" }));
+    fake.set_property("pause", true);
+    drop(fake);
+    let output = String::from_utf8(child.join().unwrap().stdout).unwrap();
+    let events = output.lines()
+        .map(|line| line.parse::<serde_json::Value>().unwrap()["event"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(events, ["property-change", "seek", "property-change"]);
+}
+
+#[test]
+fn one_shot_commands_disable_events() {
+    let fake = FakeMpv::new().unwrap();
+    stdout(&fake, &["pause"]);
+    assert_eq!(fake.requests(), [json!(["disable_event", "all"]), json!(["set_property", "pause", true])]);
+}
+
+#[test]
+fn unsupported_command() {
+    let fake = FakeMpv::new().unwrap();
+    fake.set_property("mpv-version", "mpv 0.33.1");
+    fake.set_property("command-list", json!([{ "name": "loadfile", "args": [] }]));
+    let output = mpvc(&fake, &["run", "keybind", "x", "quit"]);
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("mpvc: mpv 0.33.1 doesn't support the command 'keybind'\n"), "{stderr}");
+}
+
+#[test]
+fn timeout() {
+    let fake = FakeMpv::new().unwrap();
+    fake.on_command("hang", |_| Reply::none());
+    let output = mpvc(&fake, &["--timeout", "0.2", "run", "hang"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "mpvc: mpv didn't respond in time\n");
+    for timeout in ["0", "-1", "nan"] {
+        assert_eq!(mpvc(&fake, &["--timeout", timeout, "play"]).status.code(), Some(2));
+    }
+}
@@ -0,0 +1,637 @@
+use core::time::Duration;
+use std::io::Write as _;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use mpvc::testing::{FakeMpv, Replay, Reply};
+use mpvc::{Capabilities, Change, Error, Event, LoadMode, LogLevel, LogMessage, Mpv, MpvCommand, MpvErrorKind, MpvProcess, MpvVersion, Player, PlayerState, PlaylistEntry, Seek, SharedMpv};
+use serde_json::{Value, json};
+
+fn fake() -> FakeMpv {
+    FakeMpv::new().unwrap()
+}
+
+#[test]
+fn get_and_set_property() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    assert_eq!(mpv.get_property("pause").unwrap(), false);
+    mpv.set_property("pause", true).unwrap();
+    assert_eq!(fake.get_property("pause"), Some(true.into()));
+    mpv.add_property("volume", -20.0).unwrap();
+    assert_eq!(mpv.get_property_as::<f64>("volume").unwrap(), 80.0);
+    let Err(Error::MpvError(e)) = mpv.get_property("no-such-property") else { panic!() };
+    assert_eq!(e.kind, MpvErrorKind::PropertyNotFound);
+    assert_eq!(e.property(), Some("no-such-property"));
+    let Err(Error::MpvError(e)) = mpv.get_property("path") else { panic!() };
+    assert_eq!(e.kind, MpvErrorKind::PropertyUnavailable);
+}
+
+#[test]
+fn events_during_command_are_queued() {
+    let fake = fake();
+    fake.on_command("expand-path", |args| {
+        Reply::ok(args[0].clone())
+            .with_event(json!({ "event": "seek" }))
+            .with_event(json!({ "event": "playback-restart" }))
+    });
+    let mut mpv = fake.connect().unwrap();
+    assert_eq!(mpv.command_value("expand-path", ["~~/input.conf"]).unwrap(), "~~/input.conf");
+    assert_eq!(mpv.listen_event().unwrap(), Event::Seek);
+    assert_eq!(mpv.listen_event().unwrap(), Event::PlaybackRestart);
+    assert_eq!(mpv.poll_event().unwrap(), None);
+}
+
+#[test]
+fn failed_command_keeps_connection_usable() {
+    let fake = fake();
+    fake.on_command("fail", |_| Reply::error("error running command"));
+    let mut mpv = fake.connect().unwrap();
+    let Err(Error::MpvError(e)) = mpv.command("fail") else { panic!() };
+    assert_eq!(e.kind, MpvErrorKind::CommandFailed);
+    assert_eq!(e.command, json!(["fail"]));
+    assert_eq!(mpv.get_property("mute").unwrap(), false);
+}
+
+#[test]
+fn observe_property() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    mpv.observe_property(1, "pause").unwrap();
+    let initial = mpv.listen_event().unwrap();
+    assert_eq!(initial, Event::PropertyChange { id: 1, name: "pause".to_owned(), data: false.into() });
+
+    fake.set_property("pause", true);
+    let change = mpv.listen_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(change, Some(Event::PropertyChange { id: 1, name: "pause".to_owned(), data: true.into() }));
+
+    mpv.unobserve_property(1).unwrap();
+    fake.set_property("pause", false);
+    assert_eq!(mpv.listen_timeout(Duration::from_millis(100)).unwrap(), None);
+}
+
+#[test]
+fn playlist_commands() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    for file in ["a.flac", "b.flac", "c.flac"] {
+        mpv.command_arg("loadfile", [file, "append-play"]).unwrap();
+    }
+    assert_eq!(mpv.get_property_as::<u64>("playlist-count").unwrap(), 3);
+    assert_eq!(mpv.get_property_as::<i64>("playlist-pos").unwrap(), 0);
+
+    mpv.command("playlist-next").unwrap();
+    mpv.command_arg("playlist-move", [0, 3]).unwrap();
+    let playlist = mpv.get_property_as::<Vec<PlaylistEntry>>("playlist").unwrap();
+    let names = playlist.iter().map(PlaylistEntry::display_name).collect::<Vec<_>>();
+    assert_eq!(names, ["b.flac", "c.flac", "a.flac"]);
+    assert!(playlist[0].current);
+
+    mpv.command_arg("playlist-remove", ["current"]).unwrap();
+    assert_eq!(fake.playlist(), ["c.flac", "a.flac"]);
+    assert_eq!(mpv.get_property("path").unwrap(), "c.flac");
+}
+
+#[test]
+fn command_batch() {
+    let fake = fake();
+    fake.append(["a", "b", "c", "d"]);
+    let mut mpv = fake.connect().unwrap();
+    let count = 3;
+    let results = mpv.command_batch((0..count).map(|i| json!(["playlist-move", count, i]))).unwrap();
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(fake.playlist(), ["d", "c", "b", "a"]);
+
+    let results = mpv.command_batch([json!(["get_property", "volume"]), json!(["bogus"])]).unwrap();
+    assert_eq!(*results[0].as_ref().unwrap(), Value::from(100.0));
+    assert!(results[1].is_err());
+}
+
+#[test]
+fn file_events() {
+    let fake = fake();
+    fake.append(["a", "b"]);
+    let mut mpv = fake.connect().unwrap();
+    mpv.command("playlist-next").unwrap();
+    assert!(matches!(mpv.listen_event().unwrap(), Event::EndFile { playlist_entry_id: Some(1), .. }));
+    assert_eq!(mpv.listen_event().unwrap(), Event::StartFile { playlist_entry_id: Some(2) });
+    assert_eq!(mpv.listen_event().unwrap(), Event::FileLoaded);
+}
+
+#[test]
+fn observer_guard() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    let pause = mpv.observe("pause").unwrap();
+    let volume = mpv.observe("volume").unwrap();
+    assert_ne!(pause.id(), volume.id());
+    assert_eq!(mpv.listen_for(&volume).unwrap(), 100.0);
+    assert_eq!(mpv.listen_for(&pause).unwrap(), false);
+
+    fake.set_property("volume", 50.0);
+    fake.set_property("pause", true);
+    assert_eq!(mpv.listen_for(&pause).unwrap(), true);
+    drop(volume);
+    // Changes of the dropped observer are discarded
+    assert_eq!(mpv.poll_event().unwrap(), None);
+    assert!(fake.requests().contains(&json!(["unobserve_property", pause.id() + 1])));
+}
+
+#[test]
+fn player_state() {
+    let fake = fake();
+    fake.append(["a", "b"]);
+    let mut mpv = fake.connect().unwrap();
+    let mut state = PlayerState::new(&mut mpv, ["pause", "playlist-pos", "time-pos"]).unwrap();
+    assert_eq!(state.get_as::<bool>("pause"), Some(false));
+    assert_eq!(state.get_as::<i64>("playlist-pos"), Some(0));
+    assert_eq!(state.get("time-pos"), None);
+
+    let changed = Arc::new(Mutex::new(Vec::new()));
+    let callback = Arc::clone(&changed);
+    state.on_change(move |property, _| callback.lock().unwrap().push(property.to_owned()));
+    fake.set_property("pause", true);
+    mpv.command("playlist-next").unwrap();
+    let changes = state.changes(&mut mpv).take(2).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(changes, [
+        Change { property: "pause".to_owned(), value: true.into() },
+        Change { property: "playlist-pos".to_owned(), value: 1.into() },
+    ]);
+    assert_eq!(*changed.lock().unwrap(), ["pause", "playlist-pos"]);
+    assert_eq!(state.snapshot()["playlist-pos"], 1);
+}
+
+#[test]
+fn player() {
+    let fake = fake();
+    let mut player = Player::new(fake.connect().unwrap());
+    player.add_files(["a", "b", "c", "d"], LoadMode::AppendPlay).unwrap();
+    player.reverse_playlist().unwrap();
+    assert_eq!(fake.playlist(), ["d", "c", "b", "a"]);
+    // "a" is still the current entry
+    player.play_next(0).unwrap();
+    assert_eq!(fake.playlist(), ["c", "b", "a", "d"]);
+
+    player.toggle_pause().unwrap();
+    assert_eq!(fake.get_property("pause"), Some(true.into()));
+    player.set_loop_file(None).unwrap();
+    assert_eq!(fake.get_property("loop-file"), Some("inf".into()));
+    player.set_loop_file(None).unwrap();
+    assert_eq!(fake.get_property("loop-file"), Some(false.into()));
+    player.set_loop_playlist(Some(true)).unwrap();
+    assert_eq!(fake.get_property("loop-playlist"), Some("inf".into()));
+    player.set_mute(None).unwrap();
+    assert_eq!(fake.get_property("mute"), Some(true.into()));
+
+    player.seek(Seek::AbsolutePercent(50.0)).unwrap();
+    assert!(fake.requests().contains(&json!(["seek", 50.0, "absolute-percent"])));
+}
+
+#[test]
+fn typed_commands() {
+    let command = MpvCommand::Seek { target: Seek::Absolute(30.0), exact: Some(true) };
+    assert_eq!(Value::try_from(command).unwrap(), json!(["seek", 30.0, "absolute+exact"]));
+    let command = MpvCommand::PlaylistRemove(None);
+    assert_eq!(Value::try_from(command).unwrap(), json!(["playlist-remove", "current"]));
+    let command = MpvCommand::CycleValues { property: "loop-file".to_owned(), values: vec![false.into(), "inf".into()], reverse: true };
+    assert_eq!(Value::try_from(command).unwrap(), json!(["cycle-values", "!reverse", "loop-file", false, "inf"]));
+    // Leaving out an optional argument before a given one needs the named form
+    let command = MpvCommand::LoadFile {
+        url: "a.flac".to_owned(),
+        flags: LoadMode::Append,
+        options: vec![("start".to_owned(), "30".to_owned())],
+    };
+    assert_eq!(Value::try_from(command).unwrap(), json!({ "name": "loadfile", "url": "a.flac", "flags": "append", "options": { "start": "30" } }));
+    let command = MpvCommand::LoadFile { url: "a.flac".to_owned(), flags: LoadMode::InsertAt(2), options: Vec::new() };
+    assert_eq!(Value::try_from(command).unwrap(), json!(["loadfile", "a.flac", "insert-at", 2]));
+    let command = MpvCommand::LoadList { url: "list.m3u".to_owned(), flags: LoadMode::InsertAtPlay(0) };
+    assert_eq!(Value::try_from(command).unwrap(), json!(["loadlist", "list.m3u", "insert-at-play", 0]));
+    let command = MpvCommand::LoadList { url: "list.m3u".to_owned(), flags: LoadMode::AppendPlay };
+    assert_eq!(Value::try_from(command).unwrap(), json!(["loadlist", "list.m3u", "append-play"]));
+
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    mpv.execute(&MpvCommand::LoadFile { url: "a".to_owned(), flags: LoadMode::AppendPlay, options: Vec::new() }).unwrap();
+    mpv.execute(&MpvCommand::Add { property: "volume".to_owned(), value: -10.0 }).unwrap();
+    assert_eq!(fake.playlist(), ["a"]);
+    assert_eq!(fake.get_property("volume"), Some(90.0.into()));
+
+    // JSON has no infinity or NaN, so these are rejected instead of being sent as null
+    let requests = fake.requests().len();
+    let command = MpvCommand::Seek { target: Seek::Relative(f64::NAN), exact: None };
+    let Err(Error::UnexpectedValue) = Value::try_from(&command) else { panic!() };
+    let Err(Error::UnexpectedValue) = mpv.execute(&command) else { panic!() };
+    let Err(Error::UnexpectedValue) = mpv.execute(&MpvCommand::Multiply { property: "speed".to_owned(), factor: f64::INFINITY }) else { panic!() };
+    let Err(Error::UnexpectedValue) = mpv.add_property("volume", f64::NEG_INFINITY) else { panic!() };
+    assert_eq!(fake.requests().len(), requests);
+}
+
+#[test]
+fn hooks() {
+    let fake = fake();
+    fake.on_command("hook_add", |_| Reply::ok(Value::Null));
+    fake.on_command("hook_ack", |_| Reply::ok(Value::Null));
+    let mut mpv = fake.connect().unwrap();
+    let id = mpv.hook_add("on_load", 0, |mpv| mpv.set_property("volume", 50.0)).unwrap();
+    assert!(fake.requests().contains(&json!(["hook_add", "on_load", id, 0])));
+
+    fake.emit(json!({ "event": "hook", "hook_id": id, "id": 7 }));
+    fake.emit(json!({ "event": "file-loaded" }));
+    // The hook is handled and acknowledged without being returned
+    assert_eq!(mpv.listen_event().unwrap(), Event::FileLoaded);
+    assert_eq!(fake.get_property("volume"), Some(50.0.into()));
+    let requests = fake.requests();
+    let set = requests.iter().position(|r| r[0] == "set_property").unwrap();
+    assert_eq!(requests[set + 1], json!(["hook_ack", 7]));
+
+    // Hooks registered by hand are passed through
+    fake.emit(json!({ "event": "hook", "hook_id": 1, "id": 8 }));
+    assert_eq!(mpv.listen_event().unwrap(), Event::Hook { id: 8, hook_id: 1 });
+}
+
+#[test]
+fn hooks_during_listen_for() {
+    let fake = fake();
+    fake.on_command("hook_add", |_| Reply::ok(Value::Null));
+    fake.on_command("hook_ack", |_| Reply::ok(Value::Null));
+    let mut mpv = fake.connect().unwrap();
+    let id = mpv.hook_add("on_load", 0, |_| Ok(())).unwrap();
+    let pause = mpv.observe("pause").unwrap();
+    assert_eq!(mpv.listen_for(&pause).unwrap(), false);
+
+    fake.emit(json!({ "event": "hook", "hook_id": id, "id": 7 }));
+    fake.set_property("pause", true);
+    assert_eq!(mpv.listen_for(&pause).unwrap(), true);
+    assert!(fake.requests().contains(&json!(["hook_ack", 7])));
+
+    // Hooks which arrive while waiting for a reply are queued, and handled by the next listen
+    fake.on_command("expand-path", move |args| {
+        Reply::ok(args[0].clone()).with_event(json!({ "event": "hook", "hook_id": id, "id": 8 }))
+    });
+    mpv.command_arg("expand-path", ["~~/"]).unwrap();
+    fake.set_property("pause", false);
+    assert_eq!(mpv.listen_for(&pause).unwrap(), false);
+    assert!(fake.requests().contains(&json!(["hook_ack", 8])));
+
+    fake.emit(json!({ "event": "hook", "hook_id": id, "id": 9 }));
+    fake.emit(json!({ "event": "seek" }));
+    assert_eq!(mpv.listen_raw().unwrap().parse::<Value>().unwrap(), json!({ "event": "seek" }));
+    assert!(fake.requests().contains(&json!(["hook_ack", 9])));
+}
+
+#[test]
+fn message_handlers() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let handler = Arc::clone(&received);
+    mpv.on_message("like", move |mpv, args| {
+        handler.lock().unwrap().extend_from_slice(args);
+        mpv.set_property("mute", true)
+    });
+    let mut other = fake.connect().unwrap();
+    let name = other.client_name().unwrap();
+    mpv.script_message(["like", "5"]).unwrap();
+    mpv.script_message_to(&name, ["ignored"]).unwrap();
+    mpv.script_message(["dislike"]).unwrap();
+    // Handled messages aren't returned
+    assert_eq!(mpv.listen_event().unwrap(), Event::ClientMessage { args: vec!["dislike".to_owned()] });
+    assert_eq!(*received.lock().unwrap(), ["5"]);
+    assert_eq!(fake.get_property("mute"), Some(true.into()));
+}
+
+#[test]
+fn log_messages() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    mpv.request_log_messages(Some(LogLevel::Info)).unwrap();
+    fake.emit(json!({ "event": "seek" }));
+    fake.log(LogLevel::Debug, "cplayer", "too verbose\n");
+    fake.log(LogLevel::Warn, "ao", "underrun\n");
+    let message = mpv.listen_log().unwrap();
+    assert_eq!(message, LogMessage { prefix: "ao".to_owned(), level: LogLevel::Warn, text: "underrun\n".to_owned() });
+    // Other events are kept
+    assert_eq!(mpv.listen_event().unwrap(), Event::Seek);
+
+    mpv.request_log_messages(Some(LogLevel::Stats)).unwrap();
+    fake.log(LogLevel::Status, "cplayer", "AV: 00:00:01\n");
+    fake.log(LogLevel::Stats, "osd", "value-time\n");
+    let levels = [mpv.listen_log().unwrap().level, mpv.listen_log().unwrap().level];
+    assert_eq!(levels, [LogLevel::Status, LogLevel::Stats]);
+    assert!(LogLevel::Info < LogLevel::Status && LogLevel::Status < LogLevel::Verbose);
+
+    mpv.request_log_messages(None).unwrap();
+    fake.log(LogLevel::Fatal, "cplayer", "ignored\n");
+    assert_eq!(mpv.poll_event().unwrap(), None);
+}
+
+#[test]
+fn event_filter() {
+    let fake = fake();
+    let mut mpv = fake.connect().unwrap();
+    fake.emit(json!({ "event": "seek" }));
+    mpv.disable_event("all").unwrap();
+    mpv.enable_event("file-loaded").unwrap();
+    fake.emit(json!({ "event": "seek" }));
+    fake.emit(json!({ "event": "file-loaded" }));
+    // The seek received before disabling is discarded as well
+    assert_eq!(mpv.listen_event().unwrap(), Event::FileLoaded);
+    mpv.disable_event("file-loaded").unwrap();
+    mpv.enable_event("seek").unwrap();
+    fake.emit(json!({ "event": "file-loaded" }));
+    fake.emit(json!({ "event": "seek" }));
+    // The seek arrives, so the file-loaded sent before it was filtered out
+    assert_eq!(mpv.listen_event().unwrap(), Event::Seek);
+    assert_eq!(mpv.poll_event().unwrap(), None);
+}
+
+fn old_mpv() -> FakeMpv {
+    let fake = fake();
+    fake.set_property("mpv-version", "mpv v0.33.1-dirty");
+    fake.set_property("command-list", json!([
+        { "name": "loadfile", "args": [{ "name": "url" }, { "name": "flags" }, { "name": "options" }], "vararg": false },
+        { "name": "loadlist", "args": [{ "name": "url" }, { "name": "flags" }], "vararg": false },
+        { "name": "playlist-move", "args": [{ "name": "index1" }, { "name": "index2" }], "vararg": false },
+    ]));
+    fake.set_property("property-list", json!(["metadata", "playlist-pos", "volume"]));
+    fake
+}
+
+#[test]
+fn capabilities() {
+    let fake = old_mpv();
+    let mut mpv = fake.connect().unwrap();
+    let capabilities = mpv.capabilities().unwrap();
+    assert_eq!(capabilities.version_string(), Some("mpv v0.33.1-dirty"));
+    assert_eq!(capabilities.version(), Some(MpvVersion { major: 0, minor: 33, patch: 1 }));
+    assert!(capabilities.supports_command("loadfile"));
+    assert!(!capabilities.supports_command("playlist-play-index"));
+    assert!(!capabilities.supports_argument("loadfile", "index"));
+    assert!(capabilities.supports_property("metadata/by-key/Artist"));
+    assert!(!capabilities.supports_property("mpv-configuration"));
+    mpv.capabilities().unwrap();
+    let queries = fake.requests().iter().filter(|r| **r == json!(["get_property", "command-list"])).count();
+    assert_eq!(queries, 1);
+
+    // Without the lists, everything is assumed to be supported
+    let fake = FakeMpv::new().unwrap();
+    let mut mpv = fake.connect().unwrap();
+    let capabilities = mpv.capabilities().unwrap();
+    assert_eq!(capabilities.version(), None);
+    assert!(capabilities.supports_argument("loadfile", "index"));
+
+    // Capabilities which are set aren't queried
+    let fake = old_mpv();
+    let mut mpv = fake.connect().unwrap();
+    mpv.set_capabilities(Capabilities::latest());
+    assert!(mpv.capabilities().unwrap().supports_command("playlist-play-index"));
+    assert!(fake.requests().is_empty());
+}
+
+#[test]
+fn player_fallbacks() {
+    let fake = old_mpv();
+    fake.append(["a", "b", "c"]);
+    let mut player = Player::new(fake.connect().unwrap());
+    player.play_index(2).unwrap();
+    assert!(fake.requests().contains(&json!(["set_property", "playlist-pos", 2])));
+    assert_eq!(fake.get_property("playlist-pos"), Some(2.into()));
+
+    player.play_index(0).unwrap();
+    player.add_files(["d", "e"], LoadMode::InsertNext).unwrap();
+    assert_eq!(fake.playlist(), ["a", "d", "e", "b", "c"]);
+    player.add_files(["f"], LoadMode::InsertAt(1)).unwrap();
+    assert_eq!(fake.playlist(), ["a", "f", "d", "e", "b", "c"]);
+    player.add_files(["g"], LoadMode::InsertAt(10)).unwrap();
+    assert_eq!(fake.playlist(), ["a", "f", "d", "e", "b", "c", "g"]);
+
+    let list = std::env::temp_dir().join(format!("mpvc-list-{}.m3u", std::process::id()));
+    std::fs::write(&list, "#EXTM3U\nh\ni\n").unwrap();
+    player.add_playlists([&list], LoadMode::InsertNext).unwrap();
+    std::fs::remove_file(&list).unwrap();
+    assert_eq!(fake.playlist(), ["a", "h", "i", "f", "d", "e", "b", "c", "g"]);
+}
+
+#[test]
+fn shared_concurrent_commands() {
+    let fake = fake();
+    for i in 0..8 {
+        fake.set_property(&format!("user-data/{i}"), 0);
+    }
+    let mpv = SharedMpv::connect(fake.path()).unwrap();
+    let threads = (0..8).map(|i| {
+        let mpv = mpv.clone();
+        thread::spawn(move || {
+            let property = format!("user-data/{i}");
+            for n in 0..25 {
+                mpv.set_property(&property, n).unwrap();
+                // Each caller gets the reply to its own request
+                assert_eq!(mpv.get_property_as::<i64>(&property).unwrap(), n);
+            }
+        })
+    }).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn shared_events_interleaved_with_replies() {
+    let fake = fake();
+    fake.on_command("expand-path", |args| {
+        Reply::ok(args[0].clone())
+            .with_event(json!({ "event": "seek" }))
+            .with_event(json!({ "event": "playback-restart" }))
+    });
+    let mpv = SharedMpv::connect(fake.path()).unwrap();
+    let events = mpv.events();
+    let other = mpv.events();
+    assert_eq!(mpv.command_value("expand-path", ["~~/input.conf"]).unwrap(), "~~/input.conf");
+    assert_eq!(mpv.get_property("volume").unwrap(), 100.0);
+    // Every subscriber receives every event
+    for events in [events, other] {
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)).unwrap(), Event::Seek);
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)).unwrap(), Event::PlaybackRestart);
+    }
+}
+
+#[test]
+fn shared_reader_dies() {
+    let fake = fake();
+    fake.on_command("hang", |_| Reply::none());
+    let mpv = SharedMpv::connect(fake.path()).unwrap();
+    let events = mpv.events();
+    let handle = mpv.clone();
+    let pending = thread::spawn(move || handle.command("hang"));
+    assert!(fake.wait_for_command("hang", Duration::from_secs(5)));
+    drop(fake);
+
+    // The command in flight, the subscribers and later commands all fail
+    assert!(matches!(pending.join().unwrap(), Err(Error::ReadError(_))));
+    assert!(events.recv_timeout(Duration::from_secs(5)).is_err());
+    assert!(matches!(mpv.command("stop"), Err(Error::ReadError(_))));
+    assert!(mpv.events().recv().is_err());
+}
+
+#[test]
+fn command_timeout() {
+    let fake = fake();
+    fake.on_command("hang", |_| Reply::none());
+    let mut mpv = Mpv::connect_timeout(fake.path(), Duration::from_millis(200)).unwrap();
+    assert!(matches!(mpv.command("hang"), Err(Error::Timeout)));
+    // The connection stays usable
+    assert_eq!(mpv.get_property("volume").unwrap(), 100.0);
+    mpv.set_timeout(None).unwrap();
+    assert_eq!(mpv.get_property("mute").unwrap(), false);
+}
+
+#[test]
+fn partial_line_kept_after_timeout() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let mut mpv = Mpv::from_stream(client);
+    mpv.set_timeout(Some(Duration::from_millis(100))).unwrap();
+    server.write_all(br#"{"request_id":0,"error":"succ"#).unwrap();
+    assert!(matches!(mpv.get_property("volume"), Err(Error::Timeout)));
+    // The rest of the timed out reply is skipped, instead of being read as a line of its own
+    server.write_all(concat!(r#"ess","data":1}"#, "\n", r#"{"request_id":1,"error":"success","data":2}"#, "\n").as_bytes()).unwrap();
+    assert_eq!(mpv.get_property("volume").unwrap(), 2);
+}
+
+#[test]
+fn reconnect_restores_state() {
+    let fake = fake();
+    fake.on_command("hook_add", |_| Reply::ok(Value::Null));
+    let sent = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&sent);
+    fake.on_command("slow", move |_| {
+        let mut sent = counter.lock().unwrap();
+        *sent += 1;
+        // The first attempt is interrupted by the disconnect
+        if *sent == 1 { Reply::none() } else { Reply::ok("done") }
+    });
+    let mut mpv = fake.connect().unwrap();
+    mpv.enable_reconnect(fake.path());
+    mpv.disable_event("all").unwrap();
+    mpv.enable_event("property-change").unwrap();
+    mpv.observe_property(1, "pause").unwrap();
+    let hook = mpv.hook_add("on_load", 0, |_| Ok(())).unwrap();
+    assert!(matches!(mpv.listen_event().unwrap(), Event::PropertyChange { id: 1, .. }));
+
+    let command = thread::spawn(move || {
+        let result = mpv.command_value("slow", [] as [&str; 0]);
+        (mpv, result)
+    });
+    assert!(fake.wait_for_command("slow", Duration::from_secs(5)));
+    fake.disconnect();
+    let (mut mpv, result) = command.join().unwrap();
+    assert_eq!(result.unwrap(), "done");
+    let requests = fake.requests();
+    let first = requests.iter().position(|r| r[0] == "slow").unwrap();
+    assert_eq!(requests[first + 1..], [
+        json!(["disable_event", "all"]),
+        json!(["enable_event", "property-change"]),
+        json!(["observe_property", 1, "pause"]),
+        json!(["hook_add", "on_load", hook, 0]),
+        json!(["slow"]),
+    ]);
+    assert_eq!(mpv.listen_event().unwrap(), Event::Reconnected);
+    assert!(matches!(mpv.listen_event().unwrap(), Event::PropertyChange { id: 1, .. }));
+
+    // Lose the connection again while listening
+    let listener = thread::spawn(move || {
+        let event = mpv.listen_event();
+        (mpv, event)
+    });
+    fake.disconnect();
+    let (mut mpv, event) = listener.join().unwrap();
+    assert_eq!(event.unwrap(), Event::Reconnected);
+    assert!(matches!(mpv.listen_event().unwrap(), Event::PropertyChange { id: 1, .. }));
+    // The event filter is in place on the new connection
+    fake.emit(json!({ "event": "seek" }));
+    fake.set_property("pause", true);
+    assert_eq!(mpv.listen_event().unwrap(), Event::PropertyChange { id: 1, name: "pause".to_owned(), data: true.into() });
+}
+
+#[test]
+fn listen_raw_after_reconnect() {
+    let fake = fake();
+    fake.on_command("expand-path", |args| Reply::ok(args[0].clone()).with_event(json!({ "event": "seek" })));
+    let mut mpv = fake.connect().unwrap();
+    mpv.enable_reconnect(fake.path());
+    mpv.command_value("expand-path", ["~~/"]).unwrap();
+    fake.disconnect();
+    // The seek queued while waiting for the reply isn't lost, and the reconnected event follows
+    assert_eq!(mpv.listen_raw().unwrap(), r#"{"event":"seek"}"#);
+    assert_eq!(mpv.listen_raw().unwrap(), r#"{"event":"reconnected"}"#);
+}
+
+#[test]
+fn replay_rejects_unexpected_command() {
+    let transcript = concat!(
+        r#"{"time":0,"type":"request","message":{"command":["get_property","pause"],"request_id":0}}"#, "\n",
+        r#"{"time":0,"type":"reply","message":{"request_id":0,"error":"success","data":true}}"#, "\n",
+        r#"{"time":0,"type":"request","message":{"command":["get_property","mute"],"request_id":1}}"#, "\n",
+        r#"{"time":0,"type":"reply","message":{"request_id":1,"error":"success","data":false}}"#, "\n",
+    );
+    let replay = Replay::new(transcript.as_bytes()).unwrap();
+    let mut mpv = replay.connect().unwrap();
+    assert_eq!(mpv.get_property("pause").unwrap(), true);
+    let Err(Error::MpvError(e)) = mpv.get_property("volume") else { panic!() };
+    assert_eq!(e.message, r#"replay expected the command ["get_property","mute"]"#);
+    let result = mpv.get_property("mute");
+    assert!(matches!(result, Err(Error::ReadError(_) | Error::WriteError(_))), "{result:?}");
+}
+
+#[test]
+fn connect_tcp() {
+    let fake = FakeMpv::new_tcp().unwrap();
+    let mut mpv = Mpv::connect_tcp(fake.tcp_addr().unwrap()).unwrap();
+    mpv.set_property("pause", true).unwrap();
+    assert_eq!(fake.get_property("pause"), Some(true.into()));
+    assert!(fake.wait_for_clients(1, Duration::from_secs(5)));
+    fake.emit(json!({ "event": "seek" }));
+    assert_eq!(mpv.listen_event().unwrap(), Event::Seek);
+    let Err(Error::ConnectError(_)) = Mpv::connect_tcp(("192.0.2.1", fake.tcp_addr().unwrap().port())) else { panic!() };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn connect_abstract() {
+    let fake = FakeMpv::new_abstract().unwrap();
+    assert!(fake.path().as_os_str().is_empty());
+    let mut mpv = Mpv::connect_abstract(fake.abstract_name().unwrap()).unwrap();
+    mpv.set_property("pause", true).unwrap();
+    assert_eq!(fake.get_property("pause"), Some(true.into()));
+    assert_eq!(fake.connect().unwrap().get_property("pause").unwrap(), true);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn spawn_with_socketpair() {
+    // Stands in for mpv: answers the first command on the inherited end of the pair with whether
+    // it runs in its own session
+    let script = r#"
+        fd=${1#--input-ipc-client=fd://}
+        read -r request <&"$fd"
+        set -- $(cat /proc/$$/stat)
+        [ "$6" = "$$" ] && detached=true || detached=false
+        printf '{"request_id":0,"error":"success","data":%s}\n' "$detached" >&"$fd"
+    "#;
+    let mut mpv = MpvProcess::with_socketpair().program("sh").args(["-c", script, "sh"]).detach(true).spawn().unwrap();
+    assert_eq!(mpv.command_value("client_name", [] as [&str; 0]).unwrap(), true);
+}
+
+#[test]
+fn spawn_waits_for_socket() {
+    let fake = fake();
+    // The socket already accepts connections, so this returns right away
+    let mut mpv = MpvProcess::new(fake.path()).program("sh").args(["-c", "exec sleep 1"]).spawn().unwrap();
+    assert_eq!(mpv.get_property("volume").unwrap(), 100.0);
+
+    let path = std::env::temp_dir().join(format!("mpvc-missing-{}.sock", std::process::id()));
+    let result = MpvProcess::new(&path).program("sh").args(["-c", "exec sleep 5"]).timeout(Duration::from_millis(100)).spawn();
+    assert!(matches!(result, Err(Error::Timeout)));
+    let result = MpvProcess::new(&path).program("sh").args(["-c", "exit 1"]).spawn();
+    assert!(matches!(result, Err(Error::SpawnError(_))));
+}